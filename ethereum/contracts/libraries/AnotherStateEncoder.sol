@@ -0,0 +1,20 @@
+//SPDX-License-Identifier: Unlicense
+// Generated from the `AnotherState` Moebius state, do not edit.
+pragma solidity ^0.7.0;
+
+
+library AnotherStateEncoder {
+  // `_packedData` for the `UpdateState` instruction of the target program.
+  function pack(
+    bytes32 _id,
+    address _token,
+    uint256 _amount,
+    bool _active
+  )
+    internal
+    pure
+    returns (bytes memory)
+  {
+    return abi.encode(_id, _token, _amount, _active);
+  }
+}
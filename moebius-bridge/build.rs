@@ -1,12 +1,28 @@
 use ethers::contract::Abigen;
 
 fn main() {
-    let bindings = Abigen::new("Moebius", "./abi/moebius.json")
-        .expect("could not instantiate Abigen")
-        .generate()
-        .expect("could not generate bindings");
+    let contracts = [
+        ("Moebius", "./abi/moebius.json", "./src/bindings/moebius.rs"),
+        (
+            "SimpleContract",
+            "./abi/simple_contract.json",
+            "./src/bindings/simple_contract.rs",
+        ),
+        (
+            "UniswapOracle",
+            "./abi/uniswap_oracle.json",
+            "./src/bindings/uniswap_oracle.rs",
+        ),
+    ];
 
-    bindings
-        .write_to_file("./src/bindings/moebius.rs")
-        .expect("could not write bindings to file");
+    for (name, abi, bindings_path) in contracts.iter() {
+        let bindings = Abigen::new(name, *abi)
+            .expect("could not instantiate Abigen")
+            .generate()
+            .expect("could not generate bindings");
+
+        bindings
+            .write_to_file(bindings_path)
+            .expect("could not write bindings to file");
+    }
 }
@@ -0,0 +1,164 @@
+use crate::broadcaster::RpcBroadcaster;
+use log::{debug, info, warn};
+use serde::Deserialize;
+use solana_sdk::native_token::lamports_to_sol;
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct BalanceLimits {
+    /// Warn when the authority or an account paying for relays holds less than this.
+    #[serde(rename = "WarnLamports")]
+    pub warn_lamports: u64,
+
+    /// Hold the relays back while an account paying for relays holds less than this.
+    #[serde(rename = "FloorLamports")]
+    pub floor_lamports: Option<u64>,
+
+    #[serde(rename = "CheckIntervalSecs")]
+    pub check_interval_secs: u64,
+}
+
+impl Default for BalanceLimits {
+    fn default() -> Self {
+        Self {
+            warn_lamports: 100_000_000,
+            floor_lamports: None,
+            check_interval_secs: 60,
+        }
+    }
+}
+
+/// Regularly checks the balances of the authority and fee payers, and estimates how many
+/// relays they can still pay for. Halts the broadcaster while a fee paying account is below
+/// the floor, if there is one, which holds the relays back until the balances are topped up.
+pub struct BalanceMonitor {
+    broadcaster: RpcBroadcaster,
+    limits: BalanceLimits,
+    last_check: Option<(Instant, u64)>,
+}
+
+impl BalanceMonitor {
+    pub fn new(broadcaster: RpcBroadcaster, limits: BalanceLimits) -> BalanceMonitor {
+        Self {
+            broadcaster,
+            limits,
+            last_check: None,
+        }
+    }
+
+    pub async fn run(mut self) {
+        loop {
+            if let Err(e) = self.check().await {
+                warn!("Checking balances: {}", e);
+            }
+            tokio::time::delay_for(Duration::from_secs(self.limits.check_interval_secs)).await;
+        }
+    }
+
+    async fn check(&mut self) -> anyhow::Result<()> {
+        let authority = self.broadcaster.authority();
+        let payers = self.broadcaster.fee_paying_accounts();
+        let mut accounts = payers.clone();
+        if !payers.contains(&authority) {
+            accounts.push(authority);
+        }
+        let (balances, fee_per_relay) = self.broadcaster.balances(accounts.clone()).await?;
+
+        for (account, balance) in accounts.iter().zip(balances.iter()) {
+            if *balance < self.limits.warn_lamports {
+                warn!(
+                    "Low balance: {} holds {} SOL",
+                    account,
+                    lamports_to_sol(*balance)
+                );
+            } else {
+                debug!(
+                    "Balance: {} holds {} SOL",
+                    account,
+                    lamports_to_sol(*balance)
+                );
+            }
+        }
+
+        let payer_balances = &balances[..payers.len()];
+        let left = relays_left(payer_balances, fee_per_relay);
+        let now = Instant::now();
+        let relayed = self.broadcaster.relayed();
+        let per_hour = self.last_check.map_or(0.0, |(at, before)| {
+            relays_per_hour(relayed - before, now - at)
+        });
+        self.last_check = Some((now, relayed));
+
+        let estimate = if per_hour > 0.0 {
+            format!(
+                "~{} relays left, ~{:.1} hours at {:.0} relays per hour",
+                left,
+                left as f64 / per_hour,
+                per_hour
+            )
+        } else {
+            format!("~{} relays left", left)
+        };
+        if payer_balances
+            .iter()
+            .any(|balance| *balance < self.limits.warn_lamports)
+        {
+            warn!("Fee payers are running low: {}", estimate);
+        } else {
+            debug!("Fee payers: {}", estimate);
+        }
+
+        let below_floor = self.limits.floor_lamports.map_or(false, |floor| {
+            payer_balances.iter().any(|balance| *balance < floor)
+        });
+        if below_floor != self.broadcaster.is_halted() {
+            self.broadcaster.set_halted(below_floor);
+            if below_floor {
+                warn!("Fee payer balance below floor, holding relays back");
+            } else {
+                info!("Fee payer balances above floor again, relaying");
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// How many more relays the fee payers can pay for. They take turns, so the pool runs dry
+/// once its poorest member does.
+fn relays_left(balances: &[u64], fee_per_relay: u64) -> u64 {
+    if fee_per_relay == 0 {
+        return u64::MAX;
+    }
+
+    balances
+        .iter()
+        .min()
+        .map_or(0, |min| min / fee_per_relay * balances.len() as u64)
+}
+
+fn relays_per_hour(relays: u64, elapsed: Duration) -> f64 {
+    if elapsed == Duration::from_secs(0) {
+        return 0.0;
+    }
+
+    relays as f64 * 3600.0 / elapsed.as_secs_f64()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_relays_left() {
+        assert_eq!(relays_left(&[1_000_000], 10_000), 100);
+        // the poorest fee payer runs out first.
+        assert_eq!(relays_left(&[1_000_000, 50_000, 2_000_000], 10_000), 15);
+        assert_eq!(relays_left(&[], 10_000), 0);
+        assert_eq!(relays_left(&[1_000_000], 0), u64::MAX);
+
+        assert_eq!(relays_per_hour(10, Duration::from_secs(60)) as u64, 600);
+        assert_eq!(relays_per_hour(10, Duration::from_secs(0)) as u64, 0);
+    }
+}
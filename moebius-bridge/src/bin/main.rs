@@ -1,47 +1,165 @@
 use anyhow::anyhow;
 use ethers::{middleware::nonce_manager::NonceManagerMiddleware, prelude::*, signers::LocalWallet};
 use gumdrop::Options;
-use moebius::{Broadcaster, MoebiusWatcher};
-use serde::Deserialize;
-use solana_sdk::{pubkey::Pubkey, signature::read_keypair_file};
-use std::{convert::TryFrom, fs::File, path::PathBuf, str::FromStr, sync::Arc, time::Duration};
+use log::{info, warn};
+use moebius::{
+    profile_path, BalanceMonitor, Broadcaster, Config, ConfigLayer, EventLog, FileLease,
+    JsonlBroadcaster, Leadership, MoebiusWatcher, Reconciler, RecordedUpdate, RemoteSigner,
+    RpcBroadcaster, SharedSigner, SinkKind, StdoutBroadcaster, DEFAULT_PROFILE, ENV_PREFIX,
+};
+use solana_sdk::{
+    pubkey::Pubkey,
+    signature::{read_keypair_file, Keypair},
+};
+use std::{
+    convert::TryFrom,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+};
+use tokio::signal::unix::{signal, SignalKind};
 
 #[derive(Debug, Options, Clone)]
 struct Opts {
     help: bool,
 
-    #[options(help = "path to config json file", default = "config/localnet.json")]
-    config: PathBuf,
+    #[options(help = "config profile, reads config/<profile>.toml (default: localnet)")]
+    profile: Option<String>,
 
-    #[options(help = "path to Ethereum private key", default = "config/localnet.key")]
-    eth_private_key: PathBuf,
+    #[options(help = "path to config file, used instead of the profile's")]
+    config: Option<PathBuf>,
+
+    #[options(help = "path to Ethereum private key")]
+    eth_private_key: Option<PathBuf>,
 
     #[options(help = "path to Moebius authority key")]
-    authority_key: PathBuf,
+    authority_key: Option<PathBuf>,
+
+    #[options(help = "path to a remote signer's socket, used instead of the authority key")]
+    authority_signer: Option<PathBuf>,
+
+    #[options(help = "where to relay events: rpc (default), stdout or jsonl:<path>")]
+    sink: Option<String>,
+
+    #[options(help = "path to fee payer key, repeat to use a pool (defaults to authority)")]
+    fee_payer_key: Vec<PathBuf>,
+
+    #[options(help = "polling interval (ms, default: 1000)")]
+    interval: Option<u64>,
+
+    #[options(help = "simulate relays against Solana without ever sending them")]
+    dry_run: bool,
+
+    #[options(help = "path to checkpoint file (default: config/checkpoint.json)")]
+    checkpoint: Option<PathBuf>,
+
+    #[options(help = "seconds to wait for in-flight relays on shutdown (default: 30)")]
+    shutdown_timeout: Option<u64>,
+
+    #[options(help = "path to a leader lease file shared by all replicas, only its holder relays")]
+    leader_lease: Option<PathBuf>,
+
+    #[options(help = "seconds the leader lease lasts without renewal (default: 15)")]
+    lease_ttl: Option<u64>,
+
+    #[options(help = "name of this replica in the leader lease (default: hostname and pid)")]
+    replica_id: Option<String>,
 
-    #[options(help = "polling interval (ms)", default = "1000")]
-    interval: u64,
+    #[options(help = "path to a file to append one JSON line per processed event to")]
+    event_log: Option<PathBuf>,
+
+    #[options(command)]
+    command: Option<Command>,
 }
 
-#[derive(Deserialize)]
-struct Config {
-    #[serde(rename = "MoebiusContract")]
-    moebius_contract: Address,
+#[derive(Debug, Options, Clone)]
+enum Command {
+    #[options(help = "relay MoebiusData logs from a past range of blocks")]
+    Replay(ReplayOpts),
+    #[options(help = "relay the events recorded by a jsonl sink")]
+    Feed(FeedOpts),
+    #[options(help = "compare the configured integrations' values on Ethereum and Solana")]
+    Reconcile(ReconcileOpts),
+}
+
+#[derive(Debug, Options, Clone)]
+struct ReplayOpts {
+    help: bool,
+
+    #[options(help = "first block to fetch logs from", required)]
+    from_block: u64,
+
+    #[options(help = "last block to fetch logs from", required)]
+    to_block: u64,
 
-    #[serde(rename = "EthRpcUrl")]
-    eth_rpc_url: String,
+    #[options(help = "only relay logs for this target program (base58)")]
+    program_id: Option<String>,
 
-    #[serde(rename = "SolanaRpcUrl")]
-    solana_rpc_url: String,
+    #[options(help = "only relay logs for this target account (base58)")]
+    account_id: Option<String>,
 
-    #[serde(rename = "MoebiusAccount")]
-    moebius_account: String,
+    #[options(help = "simulate relays against Solana without ever sending them")]
+    dry_run: bool,
+}
+
+#[derive(Debug, Options, Clone)]
+struct FeedOpts {
+    help: bool,
+
+    #[options(help = "path to the JSONL file written by a jsonl sink", required)]
+    input: PathBuf,
+
+    #[options(help = "simulate relays against Solana without ever sending them")]
+    dry_run: bool,
+}
+
+#[derive(Debug, Options, Clone)]
+struct ReconcileOpts {
+    help: bool,
+
+    #[options(help = "only check the integration with this name, repeat to check several")]
+    name: Vec<String>,
+}
+
+impl Opts {
+    /// The config file, either given directly or through the profile.
+    fn config_path(&self) -> PathBuf {
+        let profile = self
+            .profile
+            .clone()
+            .or_else(|| std::env::var(format!("{}PROFILE", ENV_PREFIX)).ok())
+            .unwrap_or_else(|| DEFAULT_PROFILE.to_string());
+
+        self.config
+            .clone()
+            .unwrap_or_else(|| profile_path(&profile))
+    }
+
+    /// The config fields set on the command line.
+    fn config_layer(&self) -> ConfigLayer {
+        ConfigLayer {
+            eth_private_key: self.eth_private_key.clone(),
+            authority_key: self.authority_key.clone(),
+            authority_signer: self.authority_signer.clone(),
+            sink: self.sink.clone(),
+            fee_payer_keys: Some(self.fee_payer_key.clone()).filter(|keys| !keys.is_empty()),
+            interval_ms: self.interval,
+            dry_run: Some(true).filter(|_| self.dry_run),
+            checkpoint: self.checkpoint.clone(),
+            shutdown_timeout_secs: self.shutdown_timeout,
+            leader_lease: self.leader_lease.clone(),
+            lease_ttl_secs: self.lease_ttl,
+            replica_id: self.replica_id.clone(),
+            event_log: self.event_log.clone(),
+            ..ConfigLayer::default()
+        }
+    }
 }
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let opts = Opts::parse_args_default_or_exit();
-    let cfg: Config = serde_json::from_reader(File::open(opts.config.clone())?)?;
+    let cfg = Config::load(opts.config_path(), opts.config_layer())?;
     env_logger::init();
 
     if cfg.eth_rpc_url.starts_with("http") {
@@ -61,25 +179,249 @@ async fn run<P: JsonRpcClient + 'static>(
     opts: Opts,
     provider: Provider<P>,
 ) -> anyhow::Result<()> {
-    let provider = provider.interval(Duration::from_millis(opts.interval));
-    let signer: LocalWallet = std::fs::read_to_string(opts.eth_private_key)?.parse()?;
+    let provider = provider.interval(cfg.interval);
+    let signer: LocalWallet = std::fs::read_to_string(&cfg.eth_private_key)?.parse()?;
     let address = signer.address();
     let provider = SignerMiddleware::new(provider, signer);
     let provider = NonceManagerMiddleware::new(provider, address);
     let client = Arc::new(provider);
 
-    let keypair_path = opts
-        .authority_key
-        .to_str()
-        .ok_or_else(|| anyhow!("Keypair path not utf-8"))?;
-    let authority_key = read_keypair_file(keypair_path)
-        .map_err(|e| anyhow!("Reading solana keypair file: {}", e.to_string()))?;
-    let moebius_account = Pubkey::from_str(&cfg.moebius_account)?;
-    let broadcaster =
-        Broadcaster::new(cfg.solana_rpc_url.clone(), authority_key, moebius_account).await?;
+    if let Some(Command::Reconcile(ref reconcile)) = opts.command {
+        return reconcile_integrations(cfg, client, reconcile).await;
+    }
+
+    // Only the replica holding the lease watches for new logs.
+    let leadership = match opts.command {
+        None => cfg.leader_lease.as_ref().map(|path| {
+            Leadership::new(
+                Arc::new(FileLease::new(path)),
+                cfg.replica_id.clone().unwrap_or_else(default_replica_id),
+                cfg.lease_ttl,
+            )
+        }),
+        Some(_) => None,
+    };
+    match cfg.sink.clone() {
+        SinkKind::Rpc => {
+            let broadcaster = rpc_broadcaster(&cfg).await?;
+            if opts.command.is_none() {
+                tokio::spawn(BalanceMonitor::new(broadcaster.clone(), cfg.balances.clone()).run());
+            }
+            relay(cfg, opts, client, broadcaster, leadership).await
+        }
+        SinkKind::Stdout => relay(cfg, opts, client, StdoutBroadcaster, leadership).await,
+        SinkKind::Jsonl(path) => {
+            let broadcaster = JsonlBroadcaster::open(path)?;
+            relay(cfg, opts, client, broadcaster, leadership).await
+        }
+    }
+}
+
+async fn rpc_broadcaster(cfg: &Config) -> anyhow::Result<RpcBroadcaster> {
+    let authority: SharedSigner = match (&cfg.authority_signer, &cfg.authority_key) {
+        (Some(socket), _) => Arc::new(RemoteSigner::connect(socket)?),
+        (None, Some(path)) => Arc::new(read_keypair(path)?),
+        (None, None) => unreachable!("validated config has an authority"),
+    };
+    let fee_payers = cfg
+        .fee_payer_keys
+        .iter()
+        .map(|path| Ok(Arc::new(read_keypair(path)?) as SharedSigner))
+        .collect::<anyhow::Result<Vec<SharedSigner>>>()?;
+    let mut broadcaster = RpcBroadcaster::new(
+        cfg.solana_rpc_url.clone(),
+        authority,
+        fee_payers,
+        cfg.moebius_account,
+    )
+    .await?;
+    if let Some(max_concurrent_relays) = cfg.max_concurrent_relays {
+        broadcaster = broadcaster.with_max_concurrency(max_concurrent_relays);
+    }
 
-    let mut watcher = MoebiusWatcher::new(client, cfg.moebius_contract, broadcaster)?;
-    watcher.run().await?;
+    Ok(broadcaster)
+}
+
+/// Runs the command, or watches for new logs, relaying the events through the broadcaster.
+async fn relay<M: Middleware + 'static, B: Broadcaster>(
+    cfg: Config,
+    opts: Opts,
+    client: Arc<M>,
+    broadcaster: B,
+    leadership: Option<Leadership>,
+) -> anyhow::Result<()> {
+    match opts.command {
+        Some(Command::Replay(replay)) => {
+            let program_id = replay
+                .program_id
+                .as_deref()
+                .map(Pubkey::from_str)
+                .transpose()?;
+            let account_id = replay
+                .account_id
+                .as_deref()
+                .map(Pubkey::from_str)
+                .transpose()?;
+
+            let mut watcher = MoebiusWatcher::new(client, cfg.moebius_contract, broadcaster)?
+                .with_dry_run(cfg.dry_run || replay.dry_run)
+                .with_relay_filter(cfg.filter);
+            if let Some(ref path) = cfg.event_log {
+                watcher = watcher.with_event_log(EventLog::open(path)?);
+            }
+            watcher
+                .replay(replay.from_block, replay.to_block, program_id, account_id)
+                .await?;
+        }
+        Some(Command::Feed(feed)) => {
+            feed_recorded(&broadcaster, &feed.input, cfg.dry_run || feed.dry_run).await?
+        }
+        Some(Command::Reconcile(_)) => unreachable!("handled before relaying"),
+        None => {
+            let mut watcher = MoebiusWatcher::new(client, cfg.moebius_contract, broadcaster)?
+                .with_dry_run(cfg.dry_run)
+                .with_relay_filter(cfg.filter)
+                .with_rate_limits(cfg.rate_limits)
+                .with_checkpoint(cfg.checkpoint);
+            if let Some(shutdown_timeout) = cfg.shutdown_timeout {
+                watcher = watcher.with_shutdown_timeout(shutdown_timeout);
+            }
+            if let Some(ref path) = cfg.event_log {
+                watcher = watcher.with_event_log(EventLog::open(path)?);
+            }
+
+            match leadership {
+                // A replica that loses the lease goes back to standby.
+                Some(leadership) => loop {
+                    info!("Waiting for the leader lease");
+                    tokio::select! {
+                        acquired = leadership.acquire() => acquired?,
+                        _ = shutdown_signal() => break,
+                    }
+
+                    let lost = watcher
+                        .run_leading(shutdown_signal(), leadership.keep_renewing())
+                        .await;
+                    if leadership.is_held() {
+                        leadership.release().await?;
+                    }
+                    if !lost? {
+                        break;
+                    }
+                    warn!("Lost the leader lease, back to standby");
+                },
+                None => watcher.run(shutdown_signal()).await?,
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Relays the events recorded by a `jsonl` sink, in the order they were recorded.
+async fn feed_recorded<B: Broadcaster>(
+    broadcaster: &B,
+    input: &Path,
+    dry_run: bool,
+) -> anyhow::Result<()> {
+    let updates = RecordedUpdate::read_all(input)?;
+    info!("Feeding {} recorded events", updates.len());
+
+    for (i, update) in updates.iter().enumerate() {
+        let (program_id, account_id, data) = update.decode()?;
+        let target = format!("{}/{}", update.program_id, update.account_id);
+        if dry_run {
+            let result = broadcaster.simulate(program_id, account_id, data).await?;
+            match result.err {
+                None => info!("UpdateData (dry run) {}: ok", target),
+                Some(e) => warn!("UpdateData (dry run) {}: {}", target, e),
+            }
+            continue;
+        }
+
+        let tx_sig = broadcaster
+            .broadcast(program_id, account_id, data)
+            .await
+            .map_err(|e| anyhow!("Feeding event {} for {}: {}", i + 1, target, e))?;
+        match tx_sig {
+            Some(tx_sig) => info!("UpdateData: {}", tx_sig),
+            None => info!("UpdateData {}: recorded", target),
+        }
+    }
 
     Ok(())
 }
+
+/// Prints a report per integration, and fails if any of them drifted or is stale.
+async fn reconcile_integrations<M: Middleware + 'static>(
+    cfg: Config,
+    client: Arc<M>,
+    opts: &ReconcileOpts,
+) -> anyhow::Result<()> {
+    let integrations = cfg
+        .integrations
+        .iter()
+        .filter(|integration| opts.name.is_empty() || opts.name.contains(&integration.name))
+        .collect::<Vec<_>>();
+    if integrations.is_empty() {
+        return Err(anyhow!("No integrations to reconcile"));
+    }
+
+    let reconciler = Reconciler::new(client, cfg.solana_rpc_url.clone());
+    let mut failed = 0;
+    for integration in integrations.iter() {
+        match reconciler.reconcile(integration).await {
+            Ok(report) => {
+                println!("{}", report);
+                if !report.is_ok() {
+                    failed += 1;
+                }
+            }
+            Err(e) => {
+                println!("{}: could not reconcile: {}", integration.name, e);
+                failed += 1;
+            }
+        }
+    }
+
+    if failed > 0 {
+        return Err(anyhow!(
+            "{} of {} integrations drifted, are stale or could not be checked",
+            failed,
+            integrations.len()
+        ));
+    }
+
+    Ok(())
+}
+
+fn default_replica_id() -> String {
+    let hostname = std::env::var("HOSTNAME").unwrap_or_else(|_| "localhost".to_string());
+    format!("{}-{}", hostname, std::process::id())
+}
+
+/// Completes on the first SIGINT or SIGTERM.
+async fn shutdown_signal() {
+    let mut terminate = match signal(SignalKind::terminate()) {
+        Ok(terminate) => terminate,
+        Err(e) => {
+            warn!("Listening for SIGTERM: {}", e);
+            let _ = tokio::signal::ctrl_c().await;
+            return;
+        }
+    };
+
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => info!("Received SIGINT"),
+        _ = terminate.recv() => info!("Received SIGTERM"),
+    }
+}
+
+fn read_keypair<P: AsRef<Path>>(path: P) -> anyhow::Result<Keypair> {
+    let keypair_path = path
+        .as_ref()
+        .to_str()
+        .ok_or_else(|| anyhow!("Keypair path not utf-8"))?;
+    read_keypair_file(keypair_path)
+        .map_err(|e| anyhow!("Reading solana keypair file: {}", e.to_string()))
+}
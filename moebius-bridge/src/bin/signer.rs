@@ -0,0 +1,38 @@
+use anyhow::anyhow;
+use gumdrop::Options;
+use moebius::SignerServer;
+use solana_sdk::{pubkey::Pubkey, signature::read_keypair_file};
+use std::path::PathBuf;
+
+#[derive(Debug, Options, Clone)]
+struct Opts {
+    help: bool,
+
+    #[options(help = "path to Moebius authority key")]
+    authority_key: PathBuf,
+
+    #[options(
+        help = "path to the Unix socket to listen on",
+        default = "moebius-signer.sock"
+    )]
+    socket: PathBuf,
+
+    #[options(help = "pubkey of a fee payer the bridge uses, repeat for a pool")]
+    fee_payer: Vec<Pubkey>,
+}
+
+fn main() -> anyhow::Result<()> {
+    let opts = Opts::parse_args_default_or_exit();
+    env_logger::init();
+
+    let keypair_path = opts
+        .authority_key
+        .to_str()
+        .ok_or_else(|| anyhow!("Keypair path not utf-8"))?;
+    let authority_key = read_keypair_file(keypair_path)
+        .map_err(|e| anyhow!("Reading solana keypair file: {}", e.to_string()))?;
+
+    SignerServer::new(authority_key)
+        .with_fee_payers(opts.fee_payer)
+        .serve(opts.socket)
+}
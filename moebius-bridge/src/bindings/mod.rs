@@ -1,2 +1,6 @@
 mod moebius;
+mod simple_contract;
+mod uniswap_oracle;
 pub use moebius::*;
+pub use simple_contract::*;
+pub use uniswap_oracle::*;
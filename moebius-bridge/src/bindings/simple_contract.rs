@@ -0,0 +1,51 @@
+pub use simplecontract_mod::*;
+mod simplecontract_mod {
+    #![allow(dead_code)]
+    #![allow(unused_imports)]
+    use ethers::{
+        contract::{
+            builders::{ContractCall, Event},
+            Contract, Lazy,
+        },
+        core::{
+            abi::{parse_abi, Abi, Detokenize, InvalidOutputType, Token, Tokenizable},
+            types::*,
+        },
+        providers::Middleware,
+    };
+    #[doc = "SimpleContract was auto-generated with ethers-rs Abigen. More information at: https://github.com/gakonst/ethers-rs"]
+    use std::sync::Arc;
+    pub static SIMPLECONTRACT_ABI: Lazy<Abi> = Lazy::new(|| {
+        serde_json :: from_str ("[\n  {\n    \"inputs\": [],\n    \"name\": \"getValues\",\n    \"outputs\": [\n      {\n        \"internalType\": \"bytes32\",\n        \"name\": \"_programId\",\n        \"type\": \"bytes32\"\n      },\n      {\n        \"internalType\": \"bytes32\",\n        \"name\": \"_accountId\",\n        \"type\": \"bytes32\"\n      },\n      {\n        \"internalType\": \"bytes\",\n        \"name\": \"_packedData\",\n        \"type\": \"bytes\"\n      }\n    ],\n    \"stateMutability\": \"view\",\n    \"type\": \"function\"\n  }\n]\n") . expect ("invalid abi")
+    });
+    #[derive(Clone)]
+    pub struct SimpleContract<M>(Contract<M>);
+    impl<M> std::ops::Deref for SimpleContract<M> {
+        type Target = Contract<M>;
+        fn deref(&self) -> &Self::Target {
+            &self.0
+        }
+    }
+    impl<M: Middleware> std::fmt::Debug for SimpleContract<M> {
+        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            f.debug_tuple(stringify!(SimpleContract))
+                .field(&self.address())
+                .finish()
+        }
+    }
+    impl<'a, M: Middleware> SimpleContract<M> {
+        #[doc = r" Creates a new contract instance with the specified `ethers`"]
+        #[doc = r" client at the given `Address`. The contract derefs to a `ethers::Contract`"]
+        #[doc = r" object"]
+        pub fn new<T: Into<Address>>(address: T, client: Arc<M>) -> Self {
+            let contract = Contract::new(address.into(), SIMPLECONTRACT_ABI.clone(), client);
+            Self(contract)
+        }
+        #[doc = "Calls the contract's `getValues` (0x19eb4a90) function"]
+        pub fn get_values(&self) -> ContractCall<M, ([u8; 32], [u8; 32], Vec<u8>)> {
+            self.0
+                .method_hash([25, 235, 74, 144], ())
+                .expect("method not found (this should never happen)")
+        }
+    }
+}
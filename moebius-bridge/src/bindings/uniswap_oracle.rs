@@ -0,0 +1,81 @@
+pub use uniswaporacle_mod::*;
+mod uniswaporacle_mod {
+    #![allow(dead_code)]
+    #![allow(unused_imports)]
+    use ethers::{
+        contract::{
+            builders::{ContractCall, Event},
+            Contract, Lazy,
+        },
+        core::{
+            abi::{parse_abi, Abi, Detokenize, InvalidOutputType, Token, Tokenizable},
+            types::*,
+        },
+        providers::Middleware,
+    };
+    #[doc = "UniswapOracle was auto-generated with ethers-rs Abigen. More information at: https://github.com/gakonst/ethers-rs"]
+    use std::sync::Arc;
+    pub static UNISWAPORACLE_ABI: Lazy<Abi> = Lazy::new(|| {
+        serde_json :: from_str ("[\n  {\n    \"inputs\": [],\n    \"name\": \"accountId\",\n    \"outputs\": [\n      {\n        \"internalType\": \"bytes32\",\n        \"name\": \"\",\n        \"type\": \"bytes32\"\n      }\n    ],\n    \"stateMutability\": \"view\",\n    \"type\": \"function\"\n  },\n  {\n    \"inputs\": [],\n    \"name\": \"blockTimestampLast\",\n    \"outputs\": [\n      {\n        \"internalType\": \"uint32\",\n        \"name\": \"\",\n        \"type\": \"uint32\"\n      }\n    ],\n    \"stateMutability\": \"view\",\n    \"type\": \"function\"\n  },\n  {\n    \"inputs\": [\n      {\n        \"internalType\": \"address\",\n        \"name\": \"token\",\n        \"type\": \"address\"\n      },\n      {\n        \"internalType\": \"uint256\",\n        \"name\": \"amountIn\",\n        \"type\": \"uint256\"\n      }\n    ],\n    \"name\": \"consult\",\n    \"outputs\": [\n      {\n        \"internalType\": \"uint256\",\n        \"name\": \"amountOut\",\n        \"type\": \"uint256\"\n      }\n    ],\n    \"stateMutability\": \"view\",\n    \"type\": \"function\"\n  },\n  {\n    \"inputs\": [],\n    \"name\": \"programId\",\n    \"outputs\": [\n      {\n        \"internalType\": \"bytes32\",\n        \"name\": \"\",\n        \"type\": \"bytes32\"\n      }\n    ],\n    \"stateMutability\": \"view\",\n    \"type\": \"function\"\n  },\n  {\n    \"inputs\": [],\n    \"name\": \"token0\",\n    \"outputs\": [\n      {\n        \"internalType\": \"address\",\n        \"name\": \"\",\n        \"type\": \"address\"\n      }\n    ],\n    \"stateMutability\": \"view\",\n    \"type\": \"function\"\n  },\n  {\n    \"inputs\": [],\n    \"name\": \"token1\",\n    \"outputs\": [\n      {\n        \"internalType\": \"address\",\n        \"name\": \"\",\n        \"type\": \"address\"\n      }\n    ],\n    \"stateMutability\": \"view\",\n    \"type\": \"function\"\n  }\n]\n") . expect ("invalid abi")
+    });
+    #[derive(Clone)]
+    pub struct UniswapOracle<M>(Contract<M>);
+    impl<M> std::ops::Deref for UniswapOracle<M> {
+        type Target = Contract<M>;
+        fn deref(&self) -> &Self::Target {
+            &self.0
+        }
+    }
+    impl<M: Middleware> std::fmt::Debug for UniswapOracle<M> {
+        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            f.debug_tuple(stringify!(UniswapOracle))
+                .field(&self.address())
+                .finish()
+        }
+    }
+    impl<'a, M: Middleware> UniswapOracle<M> {
+        #[doc = r" Creates a new contract instance with the specified `ethers`"]
+        #[doc = r" client at the given `Address`. The contract derefs to a `ethers::Contract`"]
+        #[doc = r" object"]
+        pub fn new<T: Into<Address>>(address: T, client: Arc<M>) -> Self {
+            let contract = Contract::new(address.into(), UNISWAPORACLE_ABI.clone(), client);
+            Self(contract)
+        }
+        #[doc = "Calls the contract's `accountId` (0x9cfd7cff) function"]
+        pub fn account_id(&self) -> ContractCall<M, [u8; 32]> {
+            self.0
+                .method_hash([156, 253, 124, 255], ())
+                .expect("method not found (this should never happen)")
+        }
+        #[doc = "Calls the contract's `blockTimestampLast` (0xc5700a02) function"]
+        pub fn block_timestamp_last(&self) -> ContractCall<M, u32> {
+            self.0
+                .method_hash([197, 112, 10, 2], ())
+                .expect("method not found (this should never happen)")
+        }
+        #[doc = "Calls the contract's `consult` (0x3ddac953) function"]
+        pub fn consult(&self, token: Address, amount_in: U256) -> ContractCall<M, U256> {
+            self.0
+                .method_hash([61, 218, 201, 83], (token, amount_in))
+                .expect("method not found (this should never happen)")
+        }
+        #[doc = "Calls the contract's `programId` (0x98812da8) function"]
+        pub fn program_id(&self) -> ContractCall<M, [u8; 32]> {
+            self.0
+                .method_hash([152, 129, 45, 168], ())
+                .expect("method not found (this should never happen)")
+        }
+        #[doc = "Calls the contract's `token0` (0x0dfe1681) function"]
+        pub fn token_0(&self) -> ContractCall<M, Address> {
+            self.0
+                .method_hash([13, 254, 22, 129], ())
+                .expect("method not found (this should never happen)")
+        }
+        #[doc = "Calls the contract's `token1` (0xd21220a7) function"]
+        pub fn token_1(&self) -> ContractCall<M, Address> {
+            self.0
+                .method_hash([210, 18, 32, 167], ())
+                .expect("method not found (this should never happen)")
+        }
+    }
+}
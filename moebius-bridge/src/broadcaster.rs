@@ -1,39 +1,137 @@
+use crate::{rpc::SolanaRpcClient, signer::SharedSigner};
 use anyhow::anyhow;
+use async_trait::async_trait;
+use log::info;
 use moebius_program::instruction::update_data;
 use solana_client::{
-    client_error::ClientError, rpc_client::RpcClient, rpc_config::RpcSendTransactionConfig,
+    rpc_config::RpcSendTransactionConfig, rpc_response::RpcSimulateTransactionResult,
 };
 use solana_sdk::{
     commitment_config::CommitmentConfig,
+    hash::Hash,
     instruction::Instruction,
     message::Message,
     pubkey::Pubkey,
-    signature::{Keypair, Signature, Signer},
+    signature::{Signature, Signer},
     transaction::Transaction,
 };
-use tokio::task::JoinHandle;
+use std::{
+    collections::HashSet,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+use tokio::sync::{Mutex, Semaphore};
+
+/// How long a fetched blockhash is reused for. Blockhashes stay valid for much longer, but
+/// refreshing it regularly keeps the transactions well clear of expiry.
+const BLOCKHASH_TTL: Duration = Duration::from_secs(20);
+
+/// How long to wait before fetching the blockhash again, when a new one is needed right away.
+const SLOT_DURATION: Duration = Duration::from_millis(400);
+
+/// How often a halted broadcaster checks whether it can relay again.
+const HALTED_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Default maximum number of transactions in flight at any time.
+const DEFAULT_MAX_CONCURRENCY: usize = 8;
+
+/// The blockhash new transactions are signed with, along with the signatures of the
+/// transactions already sent with it.
+#[derive(Default)]
+struct BlockhashCache {
+    blockhash: Option<Hash>,
+    fetched_at: Option<Instant>,
+    signatures: HashSet<Signature>,
+}
+
+impl BlockhashCache {
+    /// The cached blockhash, if it is recent enough and is not `stale`.
+    fn get(&self, stale: Option<Hash>) -> Option<Hash> {
+        let fresh = self
+            .fetched_at
+            .map_or(false, |fetched_at| fetched_at.elapsed() < BLOCKHASH_TTL);
+        self.blockhash
+            .filter(|blockhash| fresh && Some(*blockhash) != stale)
+    }
+
+    /// Caches a newly fetched blockhash. The signatures are kept if it did not change.
+    fn refresh(&mut self, blockhash: Hash) {
+        if self.blockhash != Some(blockhash) {
+            self.signatures.clear();
+        }
+        self.blockhash = Some(blockhash);
+        self.fetched_at = Some(Instant::now());
+    }
+
+    /// Makes the next transaction fetch a new blockhash.
+    fn expire(&mut self) {
+        self.fetched_at = None;
+    }
+
+    /// Records the signature of a transaction about to be sent. Returns `false` if the
+    /// transaction was not signed with the cached blockhash, or if an identical transaction was
+    /// already sent with it: the cluster would drop it as a duplicate.
+    fn claim(&mut self, blockhash: Hash, signature: Signature) -> bool {
+        self.blockhash == Some(blockhash) && self.signatures.insert(signature)
+    }
+}
+
+/// Where the watcher hands the `MoebiusData` events it relays: a Solana cluster, or a sink that
+/// records them instead.
+#[async_trait]
+pub trait Broadcaster: Send + Sync + 'static {
+    /// Relays the packed data to the target account. Returns the transaction's signature, if
+    /// one was sent.
+    async fn broadcast(
+        &self,
+        program_id: [u8; 32],
+        account_id: [u8; 32],
+        data: Vec<u8>,
+    ) -> anyhow::Result<Option<Signature>>;
 
-type BroadcastHandle = JoinHandle<Result<Signature, ClientError>>;
+    /// Builds the same transaction as [broadcast](#tymethod.broadcast), but only simulates it.
+    /// Nothing is ever sent.
+    async fn simulate(
+        &self,
+        program_id: [u8; 32],
+        account_id: [u8; 32],
+        data: Vec<u8>,
+    ) -> anyhow::Result<RpcSimulateTransactionResult>;
+}
 
-pub struct Broadcaster {
-    authority: Keypair,
+/// Builds, signs and sends `UpdateData` transactions through a Solana RPC node. Cloning a
+/// broadcaster is cheap, and all clones share the same RPC client, blockhash cache and
+/// concurrency limit.
+#[derive(Clone)]
+pub struct RpcBroadcaster {
+    authority: SharedSigner,
+    fee_payers: Arc<Vec<SharedSigner>>,
+    next_fee_payer: Arc<AtomicUsize>,
     moebius_account: Pubkey,
-    rpc_url: String,
+    rpc_client: SolanaRpcClient,
+    blockhash: Arc<Mutex<BlockhashCache>>,
+    permits: Arc<Semaphore>,
+    relayed: Arc<AtomicU64>,
+    halted: Arc<AtomicBool>,
 }
 
-fn instruction_update_data(
+/// The instructions of the `UpdateData` transaction relaying packed data to the target account.
+pub fn instruction_update_data(
     moebius_account: &Pubkey,
     authority: &Pubkey,
     target_program: &Pubkey,
     target_account: &Pubkey,
     data: Vec<u8>,
-) -> Vec<Instruction> {
+) -> anyhow::Result<Vec<Instruction>> {
     let (caller_account, _) = Pubkey::find_program_address(
         &[&target_program.to_bytes(), &target_account.to_bytes()],
         &moebius_program::id(),
     );
 
-    let instructions = vec![update_data(
+    let instruction = update_data(
         &moebius_program::id(),
         moebius_account,
         authority,
@@ -42,77 +140,294 @@ fn instruction_update_data(
         target_account,
         data,
     )
-    .unwrap()];
+    .map_err(|e| anyhow!("Building UpdateData instruction: {}", e))?;
 
-    instructions
+    Ok(vec![instruction])
 }
 
-impl Broadcaster {
+impl RpcBroadcaster {
     pub async fn new(
         rpc_url: String,
-        authority: Keypair,
+        authority: SharedSigner,
+        fee_payers: Vec<SharedSigner>,
         moebius_account: Pubkey,
-    ) -> anyhow::Result<Broadcaster> {
+    ) -> anyhow::Result<RpcBroadcaster> {
         Ok(Self {
             authority,
+            fee_payers: Arc::new(fee_payers),
+            next_fee_payer: Arc::new(AtomicUsize::new(0)),
             moebius_account,
-            rpc_url,
+            rpc_client: SolanaRpcClient::new(rpc_url),
+            blockhash: Arc::new(Mutex::new(BlockhashCache::default())),
+            permits: Arc::new(Semaphore::new(DEFAULT_MAX_CONCURRENCY)),
+            relayed: Arc::new(AtomicU64::new(0)),
+            halted: Arc::new(AtomicBool::new(false)),
         })
     }
 
-    pub async fn broadcast(
+    /// Limits the number of transactions being built, signed or sent at the same time.
+    pub fn with_max_concurrency(mut self, max_concurrency: usize) -> Self {
+        self.permits = Arc::new(Semaphore::new(max_concurrency.max(1)));
+        self
+    }
+
+    pub fn authority(&self) -> Pubkey {
+        self.authority.pubkey()
+    }
+
+    /// The accounts paying for the transactions: the fee payers, or else the authority.
+    pub fn fee_paying_accounts(&self) -> Vec<Pubkey> {
+        if self.fee_payers.is_empty() {
+            return vec![self.authority.pubkey()];
+        }
+
+        self.fee_payers.iter().map(|payer| payer.pubkey()).collect()
+    }
+
+    /// Number of transactions sent so far.
+    pub fn relayed(&self) -> u64 {
+        self.relayed.load(Ordering::Relaxed)
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.halted.load(Ordering::SeqCst)
+    }
+
+    /// While halted, the broadcaster holds the transactions back until it is resumed.
+    pub fn set_halted(&self, halted: bool) {
+        self.halted.store(halted, Ordering::SeqCst);
+    }
+
+    /// Fetches the lamport balances of the accounts, along with the fee paid for a single
+    /// transaction.
+    pub async fn balances(&self, accounts: Vec<Pubkey>) -> anyhow::Result<(Vec<u64>, u64)> {
+        let signatures = if self.fee_payers.is_empty() { 1 } else { 2 };
+        let (_, fee_calculator) = self
+            .rpc_client
+            .get_recent_blockhash(CommitmentConfig::single_gossip())
+            .await?;
+        let balances = futures::future::try_join_all(
+            accounts
+                .iter()
+                .map(|account| self.rpc_client.get_balance(account)),
+        )
+        .await?;
+
+        Ok((balances, fee_calculator.lamports_per_signature * signatures))
+    }
+
+    /// Picks the next fee payer from the pool in a round-robin fashion. Returns `None` if no
+    /// fee payers were configured, in which case the authority pays for the transaction.
+    fn fee_payer(&self) -> Option<SharedSigner> {
+        if self.fee_payers.is_empty() {
+            return None;
+        }
+
+        let idx = self.next_fee_payer.fetch_add(1, Ordering::Relaxed) % self.fee_payers.len();
+        Some(Arc::clone(&self.fee_payers[idx]))
+    }
+
+    /// Returns the cached blockhash, or fetches a new one if it is too old or is `stale`. The
+    /// cache is not locked while fetching, so other relays keep using it meanwhile.
+    async fn recent_blockhash(&self, stale: Option<Hash>) -> anyhow::Result<Hash> {
+        loop {
+            if let Some(blockhash) = self.blockhash.lock().await.get(stale) {
+                return Ok(blockhash);
+            }
+
+            let (blockhash, _) = self
+                .rpc_client
+                .get_recent_blockhash(CommitmentConfig::single_gossip())
+                .await?;
+            if Some(blockhash) != stale {
+                let mut cache = self.blockhash.lock().await;
+                // Another relay may have refreshed the cache in the meantime.
+                if let Some(blockhash) = cache.get(stale) {
+                    return Ok(blockhash);
+                }
+                cache.refresh(blockhash);
+                return Ok(blockhash);
+            }
+            tokio::time::delay_for(SLOT_DURATION).await;
+        }
+    }
+
+    /// Builds and signs the `UpdateData` transaction for the given target. Signing runs in a
+    /// blocking task, since the authority may be a remote signer.
+    async fn signed_transaction(
         &self,
         program_id: [u8; 32],
         account_id: [u8; 32],
         data: Vec<u8>,
-    ) -> anyhow::Result<Signature> {
-        // Data that will be moved into the blocking task.
-        let rpc_url = self.rpc_url.clone();
-        let moebius_account = self.moebius_account;
-        let authority = Keypair::from_bytes(&self.authority.to_bytes()[..])?;
-        let commitment_config = CommitmentConfig::single_gossip();
-        let program_id = Pubkey::new_from_array(program_id);
-        let account_id = Pubkey::new_from_array(account_id);
-
-        let broadcast_task: BroadcastHandle = tokio::task::spawn_blocking(move || {
-            // Initialize RPC client.
-            let rpc_client = RpcClient::new(rpc_url);
-
-            // Get the recent blockhash.
-            let (recent_blockhash, _, _) = rpc_client
-                .get_recent_blockhash_with_commitment(commitment_config)?
-                .value;
-
-            // Construct the instruction for updating data via Moebius.
-            let instructions = instruction_update_data(
-                &moebius_account,
-                &authority.pubkey(),
-                &program_id,
-                &account_id,
-                data,
-            );
-
-            // Construct transaction message.
-            let message = Message::new(&instructions, Some(&authority.pubkey()));
+        blockhash: Hash,
+    ) -> anyhow::Result<Transaction> {
+        let authority = Arc::clone(&self.authority);
+        let fee_payer = self.fee_payer();
+        let instructions = instruction_update_data(
+            &self.moebius_account,
+            &authority.pubkey(),
+            &Pubkey::new_from_array(program_id),
+            &Pubkey::new_from_array(account_id),
+            data,
+        )?;
 
-            // Construct transaction.
+        tokio::task::spawn_blocking(move || -> anyhow::Result<Transaction> {
+            // Construct transaction message, paid for by the fee payer if there is one.
+            let payer = fee_payer.as_ref().unwrap_or(&authority).pubkey();
+            let message = Message::new(&instructions, Some(&payer));
             let mut transaction = Transaction::new_unsigned(message);
 
-            // Sign the transaction using authority's key.
-            transaction.try_sign(&[&authority], recent_blockhash)?;
+            // Sign the transaction using fee payer's and authority's keys.
+            let mut signers: Vec<&dyn Signer> = vec![authority.as_ref()];
+            if let Some(fee_payer) = &fee_payer {
+                signers.push(fee_payer.as_ref());
+            }
+            transaction.try_sign(&signers, blockhash)?;
+
+            Ok(transaction)
+        })
+        .await?
+    }
+
+    /// Builds and signs a transaction that differs from all the ones sent before it with the
+    /// same blockhash, fetching a newer blockhash when needed.
+    async fn unique_transaction(
+        &self,
+        program_id: [u8; 32],
+        account_id: [u8; 32],
+        data: Vec<u8>,
+    ) -> anyhow::Result<Transaction> {
+        let mut stale = None;
+        loop {
+            let blockhash = self.recent_blockhash(stale).await?;
+            let transaction = self
+                .signed_transaction(program_id, account_id, data.clone(), blockhash)
+                .await?;
+            if self
+                .blockhash
+                .lock()
+                .await
+                .claim(blockhash, transaction.signatures[0])
+            {
+                return Ok(transaction);
+            }
+            stale = Some(blockhash);
+        }
+    }
 
-            // Send transaction.
-            Ok(rpc_client.send_transaction_with_config(
+    /// Sends the `UpdateData` transaction. On failure the blockhash may have expired, so a fresh
+    /// one is fetched for the next transaction.
+    async fn send_update(
+        &self,
+        program_id: [u8; 32],
+        account_id: [u8; 32],
+        data: Vec<u8>,
+    ) -> anyhow::Result<Signature> {
+        let transaction = self
+            .unique_transaction(program_id, account_id, data)
+            .await?;
+        let result = self
+            .rpc_client
+            .send_transaction(
                 &transaction,
                 RpcSendTransactionConfig {
-                    preflight_commitment: Some(commitment_config.commitment),
+                    preflight_commitment: Some(CommitmentConfig::single_gossip().commitment),
                     ..RpcSendTransactionConfig::default()
                 },
-            )?)
-        });
+            )
+            .await;
+        if result.is_err() {
+            self.blockhash.lock().await.expire();
+        }
+
+        result
+    }
+
+    async fn simulate_update(
+        &self,
+        program_id: [u8; 32],
+        account_id: [u8; 32],
+        data: Vec<u8>,
+    ) -> anyhow::Result<RpcSimulateTransactionResult> {
+        let blockhash = self.recent_blockhash(None).await?;
+        let transaction = self
+            .signed_transaction(program_id, account_id, data, blockhash)
+            .await?;
+
+        self.rpc_client
+            .simulate_transaction(&transaction, true)
+            .await
+    }
+}
+
+#[async_trait]
+impl Broadcaster for RpcBroadcaster {
+    async fn broadcast(
+        &self,
+        program_id: [u8; 32],
+        account_id: [u8; 32],
+        data: Vec<u8>,
+    ) -> anyhow::Result<Option<Signature>> {
+        if self.is_halted() {
+            info!(
+                "Holding relay to {} while fee payer balance is below floor",
+                Pubkey::new_from_array(account_id)
+            );
+            while self.is_halted() {
+                tokio::time::delay_for(HALTED_CHECK_INTERVAL).await;
+            }
+        }
+
+        let _permit = self.permits.acquire().await;
+        let signature = self
+            .send_update(program_id, account_id, data)
+            .await
+            .map_err(|e| anyhow!("Broadcast tx: {}", e))?;
+        self.relayed.fetch_add(1, Ordering::Relaxed);
+
+        Ok(Some(signature))
+    }
+
+    /// Simulates the signed transaction against the cluster.
+    async fn simulate(
+        &self,
+        program_id: [u8; 32],
+        account_id: [u8; 32],
+        data: Vec<u8>,
+    ) -> anyhow::Result<RpcSimulateTransactionResult> {
+        let _permit = self.permits.acquire().await;
+        self.simulate_update(program_id, account_id, data)
+            .await
+            .map_err(|e| anyhow!("Simulate tx: {}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blockhash_cache() {
+        let mut cache = BlockhashCache::default();
+        let (first, second) = (Hash::new(&[1u8; 32]), Hash::new(&[2u8; 32]));
+        let signature = Signature::new(&[3u8; 64]);
+        assert_eq!(cache.get(None), None);
+
+        cache.refresh(first);
+        assert_eq!(cache.get(None), Some(first));
+        assert_eq!(cache.get(Some(first)), None);
+        assert!(cache.claim(first, signature));
+        // the same transaction again would be dropped by the cluster.
+        assert!(!cache.claim(first, signature));
+        // fetching the same blockhash again keeps the signatures.
+        cache.refresh(first);
+        assert!(!cache.claim(first, signature));
+
+        cache.refresh(second);
+        assert!(!cache.claim(first, Signature::new(&[4u8; 64])));
+        assert!(cache.claim(second, signature));
 
-        Ok(broadcast_task
-            .await?
-            .map_err(|e| anyhow!("Broadcast tx: {}", e.to_string()))?)
+        cache.expire();
+        assert_eq!(cache.get(None), None);
     }
 }
@@ -0,0 +1,120 @@
+use crate::{bindings::MoebiusDataFilter, event_log::EventSource};
+use anyhow::anyhow;
+use rustc_hex::{FromHex, ToHex};
+use serde::{Deserialize, Serialize};
+use solana_sdk::pubkey::Pubkey;
+use std::{convert::TryFrom, fs::File, io::ErrorKind, path::Path, str::FromStr};
+
+/// What the bridge persists when it stops, so that it can resume where it left off.
+#[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Checkpoint {
+    /// The last Ethereum block whose `MoebiusData` logs have all been handled. Their relays
+    /// have either finished, or are part of `pending`.
+    #[serde(rename = "Block")]
+    pub block: Option<u64>,
+
+    /// Events that were waiting to be relayed, or whose relay did not finish in time.
+    #[serde(rename = "Pending", default)]
+    pub pending: Vec<PendingRelay>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct PendingRelay {
+    #[serde(rename = "ProgramId")]
+    pub program_id: String,
+
+    #[serde(rename = "AccountId")]
+    pub account_id: String,
+
+    #[serde(rename = "PackedData")]
+    pub packed_data: String,
+
+    #[serde(flatten)]
+    pub source: EventSource,
+}
+
+impl PendingRelay {
+    pub fn new(data: &MoebiusDataFilter, source: EventSource) -> PendingRelay {
+        Self {
+            program_id: Pubkey::new_from_array(data.program_id).to_string(),
+            account_id: Pubkey::new_from_array(data.account_id).to_string(),
+            packed_data: data.packed_data.to_hex(),
+            source,
+        }
+    }
+}
+
+impl TryFrom<&PendingRelay> for MoebiusDataFilter {
+    type Error = anyhow::Error;
+
+    fn try_from(pending: &PendingRelay) -> anyhow::Result<Self> {
+        Ok(Self {
+            program_id: Pubkey::from_str(&pending.program_id)?.to_bytes(),
+            account_id: Pubkey::from_str(&pending.account_id)?.to_bytes(),
+            packed_data: pending
+                .packed_data
+                .from_hex()
+                .map_err(|e| anyhow!("Decoding packed data: {}", e))?,
+        })
+    }
+}
+
+impl Checkpoint {
+    /// Loads the checkpoint, or returns an empty one if there is none yet.
+    pub fn load<P: AsRef<Path>>(path: P) -> anyhow::Result<Checkpoint> {
+        match File::open(path) {
+            Ok(file) => Ok(serde_json::from_reader(file)?),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Checkpoint::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Writes the checkpoint to a temporary file first, and then moves it in place, so that a
+    /// crash never leaves a truncated checkpoint behind.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        let tmp_path = path.with_extension("tmp");
+        serde_json::to_writer_pretty(File::create(&tmp_path)?, self)?;
+        std::fs::rename(tmp_path, path)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_save_and_load() {
+        let path =
+            std::env::temp_dir().join(format!("moebius-checkpoint-{}.json", std::process::id()));
+        assert_eq!(Checkpoint::load(&path).unwrap(), Checkpoint::default());
+
+        let data = MoebiusDataFilter {
+            program_id: [1u8; 32],
+            account_id: [2u8; 32],
+            packed_data: vec![3u8; 96],
+        };
+        let checkpoint = Checkpoint {
+            block: Some(42),
+            pending: vec![PendingRelay::new(
+                &data,
+                EventSource {
+                    block: Some(41),
+                    ..EventSource::default()
+                },
+            )],
+        };
+        checkpoint.save(&path).unwrap();
+
+        let loaded = Checkpoint::load(&path).unwrap();
+        assert_eq!(loaded, checkpoint);
+        assert_eq!(
+            MoebiusDataFilter::try_from(&loaded.pending[0]).unwrap(),
+            data
+        );
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,511 @@
+use crate::{
+    balance::BalanceLimits, filter::RelayFilter, reconcile::Integration, sink::SinkKind,
+    throttle::RateLimits,
+};
+use anyhow::anyhow;
+use ethers::types::Address;
+use serde::Deserialize;
+use solana_sdk::pubkey::Pubkey;
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+
+/// Prefix of the environment variables overriding config fields, e.g. `MOEBIUS_ETH_RPC_URL`.
+pub const ENV_PREFIX: &str = "MOEBIUS_";
+
+/// Profile used when neither `--profile` nor `MOEBIUS_PROFILE` is set.
+pub const DEFAULT_PROFILE: &str = "localnet";
+
+/// Path of a named profile's config file, e.g. `config/devnet.toml`.
+pub fn profile_path(profile: &str) -> PathBuf {
+    PathBuf::from(format!("config/{}.toml", profile))
+}
+
+/// One layer of the bridge config: a config file, the environment or the command line. Every
+/// field is optional, so that the fields set in a later layer override the earlier ones.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigLayer {
+    #[serde(rename = "MoebiusContract")]
+    pub moebius_contract: Option<String>,
+
+    #[serde(rename = "EthRpcUrl")]
+    pub eth_rpc_url: Option<String>,
+
+    #[serde(rename = "SolanaRpcUrl")]
+    pub solana_rpc_url: Option<String>,
+
+    #[serde(rename = "MoebiusAccount")]
+    pub moebius_account: Option<String>,
+
+    #[serde(rename = "EthPrivateKey")]
+    pub eth_private_key: Option<PathBuf>,
+
+    #[serde(rename = "AuthorityKey")]
+    pub authority_key: Option<PathBuf>,
+
+    #[serde(rename = "AuthoritySigner")]
+    pub authority_signer: Option<PathBuf>,
+
+    #[serde(rename = "Sink")]
+    pub sink: Option<String>,
+
+    #[serde(rename = "FeePayerKeys")]
+    pub fee_payer_keys: Option<Vec<PathBuf>>,
+
+    #[serde(rename = "IntervalMs")]
+    pub interval_ms: Option<u64>,
+
+    #[serde(rename = "DryRun")]
+    pub dry_run: Option<bool>,
+
+    #[serde(rename = "Checkpoint")]
+    pub checkpoint: Option<PathBuf>,
+
+    #[serde(rename = "ShutdownTimeoutSecs")]
+    pub shutdown_timeout_secs: Option<u64>,
+
+    #[serde(rename = "LeaderLease")]
+    pub leader_lease: Option<PathBuf>,
+
+    #[serde(rename = "LeaseTtlSecs")]
+    pub lease_ttl_secs: Option<u64>,
+
+    #[serde(rename = "ReplicaId")]
+    pub replica_id: Option<String>,
+
+    #[serde(rename = "EventLog")]
+    pub event_log: Option<PathBuf>,
+
+    #[serde(rename = "Filter")]
+    pub filter: Option<RelayFilter>,
+
+    #[serde(rename = "RateLimits")]
+    pub rate_limits: Option<RateLimits>,
+
+    #[serde(rename = "MaxConcurrentRelays")]
+    pub max_concurrent_relays: Option<usize>,
+
+    #[serde(rename = "Balances")]
+    pub balances: Option<BalanceLimits>,
+
+    #[serde(rename = "Integrations")]
+    pub integrations: Option<Vec<Integration>>,
+}
+
+impl ConfigLayer {
+    /// Reads a TOML config file, or a JSON one if its extension is `.json`.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> anyhow::Result<ConfigLayer> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| anyhow!("Reading config file {}: {}", path.display(), e))?;
+
+        let layer = if path.extension().map_or(false, |ext| ext == "json") {
+            serde_json::from_str(&contents).map_err(|e| e.to_string())
+        } else {
+            toml::from_str(&contents).map_err(|e| e.to_string())
+        };
+        layer.map_err(|e| anyhow!("Parsing config file {}: {}", path.display(), e))
+    }
+
+    /// Reads the `MOEBIUS_*` environment variables. `Filter`, `RateLimits`, `Balances` and
+    /// `Integrations` can only be set in a config file.
+    pub fn from_env() -> anyhow::Result<ConfigLayer> {
+        Self::from_vars(std::env::vars())
+    }
+
+    fn from_vars<I: IntoIterator<Item = (String, String)>>(vars: I) -> anyhow::Result<ConfigLayer> {
+        let mut layer = ConfigLayer::default();
+        let mut errors = Vec::new();
+        for (key, value) in vars {
+            if key.starts_with(ENV_PREFIX) {
+                if let Err(e) = layer.set_var(&key, value) {
+                    errors.push(e.to_string());
+                }
+            }
+        }
+
+        if !errors.is_empty() {
+            return Err(anyhow!("Invalid environment:\n  {}", errors.join("\n  ")));
+        }
+
+        Ok(layer)
+    }
+
+    fn set_var(&mut self, key: &str, value: String) -> anyhow::Result<()> {
+        match &key[ENV_PREFIX.len()..] {
+            "MOEBIUS_CONTRACT" => self.moebius_contract = Some(value),
+            "ETH_RPC_URL" => self.eth_rpc_url = Some(value),
+            "SOLANA_RPC_URL" => self.solana_rpc_url = Some(value),
+            "MOEBIUS_ACCOUNT" => self.moebius_account = Some(value),
+            "ETH_PRIVATE_KEY" => self.eth_private_key = Some(value.into()),
+            "AUTHORITY_KEY" => self.authority_key = Some(value.into()),
+            "AUTHORITY_SIGNER" => self.authority_signer = Some(value.into()),
+            "SINK" => self.sink = Some(value),
+            "FEE_PAYER_KEYS" => {
+                self.fee_payer_keys = Some(value.split(',').map(PathBuf::from).collect())
+            }
+            "INTERVAL_MS" => self.interval_ms = Some(parse_var(key, &value)?),
+            "DRY_RUN" => self.dry_run = Some(parse_var(key, &value)?),
+            "CHECKPOINT" => self.checkpoint = Some(value.into()),
+            "SHUTDOWN_TIMEOUT_SECS" => self.shutdown_timeout_secs = Some(parse_var(key, &value)?),
+            "LEADER_LEASE" => self.leader_lease = Some(value.into()),
+            "LEASE_TTL_SECS" => self.lease_ttl_secs = Some(parse_var(key, &value)?),
+            "REPLICA_ID" => self.replica_id = Some(value),
+            "EVENT_LOG" => self.event_log = Some(value.into()),
+            "MAX_CONCURRENT_RELAYS" => self.max_concurrent_relays = Some(parse_var(key, &value)?),
+            // selects the config file.
+            "PROFILE" => {}
+            _ => return Err(anyhow!("{}: unknown variable", key)),
+        }
+
+        Ok(())
+    }
+
+    /// Overrides the fields of this layer with the ones set in `other`.
+    pub fn merge(self, other: ConfigLayer) -> ConfigLayer {
+        ConfigLayer {
+            moebius_contract: other.moebius_contract.or(self.moebius_contract),
+            eth_rpc_url: other.eth_rpc_url.or(self.eth_rpc_url),
+            solana_rpc_url: other.solana_rpc_url.or(self.solana_rpc_url),
+            moebius_account: other.moebius_account.or(self.moebius_account),
+            eth_private_key: other.eth_private_key.or(self.eth_private_key),
+            authority_key: other.authority_key.or(self.authority_key),
+            authority_signer: other.authority_signer.or(self.authority_signer),
+            sink: other.sink.or(self.sink),
+            fee_payer_keys: other.fee_payer_keys.or(self.fee_payer_keys),
+            interval_ms: other.interval_ms.or(self.interval_ms),
+            dry_run: other.dry_run.or(self.dry_run),
+            checkpoint: other.checkpoint.or(self.checkpoint),
+            shutdown_timeout_secs: other.shutdown_timeout_secs.or(self.shutdown_timeout_secs),
+            leader_lease: other.leader_lease.or(self.leader_lease),
+            lease_ttl_secs: other.lease_ttl_secs.or(self.lease_ttl_secs),
+            replica_id: other.replica_id.or(self.replica_id),
+            event_log: other.event_log.or(self.event_log),
+            filter: other.filter.or(self.filter),
+            rate_limits: other.rate_limits.or(self.rate_limits),
+            max_concurrent_relays: other.max_concurrent_relays.or(self.max_concurrent_relays),
+            balances: other.balances.or(self.balances),
+            integrations: other.integrations.or(self.integrations),
+        }
+    }
+
+    /// Checks every field, and reports all the invalid ones at once.
+    pub fn validate(self) -> anyhow::Result<Config> {
+        let mut errors = Vec::new();
+
+        let moebius_contract = parse_field(
+            &mut errors,
+            "MoebiusContract",
+            "Address",
+            &self.moebius_contract,
+            |value| Address::from_str(value.trim_start_matches("0x")),
+        );
+        let moebius_account = parse_field(
+            &mut errors,
+            "MoebiusAccount",
+            "Pubkey",
+            &self.moebius_account,
+            Pubkey::from_str,
+        );
+        let eth_rpc_url = url_field(&mut errors, "EthRpcUrl", &["http", "ws"], self.eth_rpc_url);
+        let solana_rpc_url = url_field(&mut errors, "SolanaRpcUrl", &["http"], self.solana_rpc_url);
+
+        let eth_private_key = self.eth_private_key;
+        match eth_private_key {
+            Some(ref path) => check_file(&mut errors, "EthPrivateKey", path),
+            None => errors.push("EthPrivateKey: missing".to_string()),
+        }
+        let sink = match self.sink {
+            Some(_) => parse_field(&mut errors, "Sink", "sink", &self.sink, SinkKind::from_str),
+            None => Some(SinkKind::default()),
+        };
+        // Only sending transactions takes the authority's signature.
+        if sink == Some(SinkKind::Rpc) {
+            match (&self.authority_key, &self.authority_signer) {
+                (_, Some(_)) => {}
+                (Some(path), None) => check_file(&mut errors, "AuthorityKey", path),
+                (None, None) => {
+                    errors.push("AuthorityKey: missing, or set AuthoritySigner".to_string())
+                }
+            }
+        }
+        let fee_payer_keys = self.fee_payer_keys.unwrap_or_default();
+        for path in fee_payer_keys.iter() {
+            check_file(&mut errors, "FeePayerKeys", path);
+        }
+
+        if self.interval_ms == Some(0) {
+            errors.push("IntervalMs: must be greater than 0".to_string());
+        }
+        if self.leader_lease.is_some() && self.lease_ttl_secs == Some(0) {
+            errors.push("LeaseTtlSecs: must be greater than 0".to_string());
+        }
+        // The replica taking over resumes from the checkpoint the previous holder left.
+        if self.leader_lease.is_some() && self.checkpoint.is_none() {
+            errors.push(
+                "Checkpoint: missing, set it to a path all replicas share with LeaderLease"
+                    .to_string(),
+            );
+        }
+        if self.max_concurrent_relays == Some(0) {
+            errors.push("MaxConcurrentRelays: must be greater than 0".to_string());
+        }
+
+        let balances = self.balances.unwrap_or_default();
+        if balances.check_interval_secs == 0 {
+            errors.push("Balances.CheckIntervalSecs: must be greater than 0".to_string());
+        }
+
+        let integrations = self.integrations.unwrap_or_default();
+        for (i, integration) in integrations.iter().enumerate() {
+            if integrations[..i]
+                .iter()
+                .any(|other| other.name == integration.name)
+            {
+                errors.push(format!(
+                    "Integrations: `{}` is configured twice",
+                    integration.name
+                ));
+            }
+            if integration.max_age_secs == Some(0) {
+                errors.push(format!(
+                    "Integrations.{}.MaxAgeSecs: must be greater than 0",
+                    integration.name
+                ));
+            }
+            if integration.tolerance < 0.0 {
+                errors.push(format!(
+                    "Integrations.{}.Tolerance: must not be negative",
+                    integration.name
+                ));
+            }
+        }
+
+        if !errors.is_empty() {
+            return Err(anyhow!("Invalid config:\n  {}", errors.join("\n  ")));
+        }
+
+        Ok(Config {
+            moebius_contract: moebius_contract.unwrap(),
+            eth_rpc_url: eth_rpc_url.unwrap(),
+            solana_rpc_url: solana_rpc_url.unwrap(),
+            moebius_account: moebius_account.unwrap(),
+            eth_private_key: eth_private_key.unwrap(),
+            authority_key: self.authority_key,
+            authority_signer: self.authority_signer,
+            sink: sink.unwrap(),
+            fee_payer_keys,
+            interval: Duration::from_millis(self.interval_ms.unwrap_or(1000)),
+            dry_run: self.dry_run.unwrap_or(false),
+            checkpoint: self
+                .checkpoint
+                .unwrap_or_else(|| PathBuf::from("config/checkpoint.json")),
+            shutdown_timeout: self.shutdown_timeout_secs.map(Duration::from_secs),
+            leader_lease: self.leader_lease,
+            lease_ttl: Duration::from_secs(self.lease_ttl_secs.unwrap_or(15)),
+            replica_id: self.replica_id,
+            event_log: self.event_log,
+            filter: self.filter.unwrap_or_default(),
+            rate_limits: self.rate_limits.unwrap_or_default(),
+            max_concurrent_relays: self.max_concurrent_relays,
+            balances,
+            integrations,
+        })
+    }
+}
+
+/// The validated bridge config.
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub moebius_contract: Address,
+    pub eth_rpc_url: String,
+    pub solana_rpc_url: String,
+    pub moebius_account: Pubkey,
+    pub eth_private_key: PathBuf,
+    pub authority_key: Option<PathBuf>,
+    /// Takes precedence over `authority_key`.
+    pub authority_signer: Option<PathBuf>,
+    /// The authority is only needed for the `Rpc` sink.
+    pub sink: SinkKind,
+    pub fee_payer_keys: Vec<PathBuf>,
+    pub interval: Duration,
+    pub dry_run: bool,
+    pub checkpoint: PathBuf,
+    pub shutdown_timeout: Option<Duration>,
+    pub leader_lease: Option<PathBuf>,
+    pub lease_ttl: Duration,
+    pub replica_id: Option<String>,
+    /// Where to append one JSON line per processed event.
+    pub event_log: Option<PathBuf>,
+    pub filter: RelayFilter,
+    pub rate_limits: RateLimits,
+    pub max_concurrent_relays: Option<usize>,
+    pub balances: BalanceLimits,
+    /// Checked by the `reconcile` command.
+    pub integrations: Vec<Integration>,
+}
+
+impl Config {
+    /// Layers the config file, the environment and the command line, in that order.
+    pub fn load<P: AsRef<Path>>(path: P, cli: ConfigLayer) -> anyhow::Result<Config> {
+        ConfigLayer::from_file(path)?
+            .merge(ConfigLayer::from_env()?)
+            .merge(cli)
+            .validate()
+    }
+}
+
+fn parse_var<T: FromStr>(key: &str, value: &str) -> anyhow::Result<T>
+where
+    T::Err: std::fmt::Display,
+{
+    value
+        .parse()
+        .map_err(|e| anyhow!("{}: invalid value `{}`: {}", key, value, e))
+}
+
+fn parse_field<T, E, F>(
+    errors: &mut Vec<String>,
+    field: &str,
+    type_name: &str,
+    value: &Option<String>,
+    parse: F,
+) -> Option<T>
+where
+    E: std::fmt::Display,
+    F: FnOnce(&str) -> Result<T, E>,
+{
+    let value = match value {
+        Some(value) => value,
+        None => {
+            errors.push(format!("{}: missing", field));
+            return None;
+        }
+    };
+
+    match parse(value) {
+        Ok(parsed) => Some(parsed),
+        Err(e) => {
+            errors.push(format!(
+                "{}: `{}` is not a valid {}: {}",
+                field, value, type_name, e
+            ));
+            None
+        }
+    }
+}
+
+fn url_field(
+    errors: &mut Vec<String>,
+    field: &str,
+    schemes: &[&str],
+    value: Option<String>,
+) -> Option<String> {
+    match value {
+        Some(url) if schemes.iter().any(|scheme| url.starts_with(scheme)) => Some(url),
+        Some(url) => {
+            errors.push(format!(
+                "{}: `{}` is not a valid URL, expected {}",
+                field,
+                url,
+                schemes.join(" or ")
+            ));
+            None
+        }
+        None => {
+            errors.push(format!("{}: missing", field));
+            None
+        }
+    }
+}
+
+fn check_file(errors: &mut Vec<String>, field: &str, path: &Path) {
+    if !path.is_file() {
+        errors.push(format!("{}: {} does not exist", field, path.display()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layers() {
+        let file: ConfigLayer = toml::from_str(
+            r#"
+            EthRpcUrl = "http://127.0.0.1:8545"
+            SolanaRpcUrl = "http://127.0.0.1:8899"
+            IntervalMs = 500
+
+            [RateLimits]
+            MinIntervalMs = 30000
+            "#,
+        )
+        .unwrap();
+        let env = ConfigLayer::from_vars(vec![
+            (
+                "MOEBIUS_SOLANA_RPC_URL".to_string(),
+                "http://solana:8899".to_string(),
+            ),
+            (
+                "MOEBIUS_FEE_PAYER_KEYS".to_string(),
+                "a.json,b.json".to_string(),
+            ),
+            ("MOEBIUS_PROFILE".to_string(), "devnet".to_string()),
+            ("HOME".to_string(), "/root".to_string()),
+        ])
+        .unwrap();
+        let cli = ConfigLayer {
+            interval_ms: Some(2000),
+            ..ConfigLayer::default()
+        };
+
+        let layer = file.merge(env).merge(cli);
+        assert_eq!(layer.eth_rpc_url.as_deref(), Some("http://127.0.0.1:8545"));
+        assert_eq!(layer.solana_rpc_url.as_deref(), Some("http://solana:8899"));
+        assert_eq!(
+            layer.fee_payer_keys,
+            Some(vec![PathBuf::from("a.json"), PathBuf::from("b.json")])
+        );
+        assert_eq!(layer.interval_ms, Some(2000));
+        assert_eq!(layer.rate_limits.unwrap().min_interval_ms, 30000);
+
+        // every invalid or unknown variable is reported.
+        let err = ConfigLayer::from_vars(vec![
+            ("MOEBIUS_DRY_RUN".to_string(), "maybe".to_string()),
+            ("MOEBIUS_INTERVALMS".to_string(), "500".to_string()),
+        ])
+        .unwrap_err()
+        .to_string();
+        assert!(err.contains("MOEBIUS_DRY_RUN: invalid value `maybe`"));
+        assert!(err.contains("MOEBIUS_INTERVALMS: unknown variable"));
+    }
+
+    #[test]
+    fn test_validate_names_fields() {
+        let layer = ConfigLayer {
+            moebius_contract: Some("0xEthereumContractAddressGoesHere".to_string()),
+            moebius_account: Some("MoebiusAccountBase58AddressGoesHere".to_string()),
+            eth_rpc_url: Some("127.0.0.1:8545".to_string()),
+            max_concurrent_relays: Some(0),
+            leader_lease: Some(PathBuf::from("leader.json")),
+            ..ConfigLayer::default()
+        };
+        let err = layer.validate().unwrap_err().to_string();
+
+        assert!(err.contains(
+            "MoebiusContract: `0xEthereumContractAddressGoesHere` is not a valid Address"
+        ));
+        assert!(err.contains(
+            "MoebiusAccount: `MoebiusAccountBase58AddressGoesHere` is not a valid Pubkey"
+        ));
+        assert!(err.contains("EthRpcUrl: `127.0.0.1:8545` is not a valid URL"));
+        assert!(err.contains("SolanaRpcUrl: missing"));
+        assert!(err.contains("AuthorityKey: missing"));
+        assert!(err.contains("MaxConcurrentRelays: must be greater than 0"));
+        assert!(err.contains("Checkpoint: missing"));
+    }
+}
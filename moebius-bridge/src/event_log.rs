@@ -0,0 +1,146 @@
+use ethers::types::{Log, H256};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{File, OpenOptions},
+    io::Write,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Where a `MoebiusData` event was emitted on Ethereum.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct EventSource {
+    #[serde(rename = "TxHash", default)]
+    pub tx_hash: Option<H256>,
+
+    #[serde(rename = "Block", default)]
+    pub block: Option<u64>,
+
+    #[serde(rename = "LogIndex", default)]
+    pub log_index: Option<u64>,
+}
+
+impl From<&Log> for EventSource {
+    fn from(log: &Log) -> Self {
+        Self {
+            tx_hash: log.transaction_hash,
+            block: log.block_number.map(|n| n.as_u64()),
+            log_index: log.log_index.map(|n| n.as_u64()),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Decision {
+    /// Sent to Solana, or simulated in dry-run mode.
+    Relayed,
+    /// Rejected by the relay filter.
+    Filtered,
+    /// Superseded by a newer event for the same target before it was relayed.
+    Coalesced,
+    /// The relay failed, the event is kept in the checkpoint.
+    Failed,
+    /// The log could not be decoded, and is dropped.
+    DeadLettered,
+}
+
+/// One line of the event log.
+#[derive(Clone, Debug, Serialize)]
+pub struct EventRecord {
+    #[serde(flatten)]
+    pub source: EventSource,
+
+    #[serde(rename = "ProgramId")]
+    pub program_id: Option<String>,
+
+    #[serde(rename = "AccountId")]
+    pub account_id: Option<String>,
+
+    /// Hex encoded packed data, or the raw log data if it could not be decoded.
+    #[serde(rename = "Payload")]
+    pub payload: String,
+
+    #[serde(rename = "Decision")]
+    pub decision: Decision,
+
+    #[serde(rename = "Signature")]
+    pub signature: Option<String>,
+
+    #[serde(rename = "Error")]
+    pub error: Option<String>,
+
+    #[serde(rename = "DryRun")]
+    pub dry_run: bool,
+
+    /// When the bridge received the log, in milliseconds since the unix epoch.
+    #[serde(rename = "ReceivedAt")]
+    pub received_at: u64,
+
+    /// How long the event waited for its target to be free, e.g. because of rate limits.
+    #[serde(rename = "QueuedMs")]
+    pub queued_ms: Option<u64>,
+
+    /// How long building, signing and sending the transaction took.
+    #[serde(rename = "RelayMs")]
+    pub relay_ms: Option<u64>,
+}
+
+/// Appends one JSON line per processed `MoebiusData` event to a file.
+pub struct EventLog {
+    file: File,
+}
+
+impl EventLog {
+    pub fn open<P: AsRef<Path>>(path: P) -> anyhow::Result<EventLog> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+
+        Ok(Self { file })
+    }
+
+    pub fn write(&mut self, record: &EventRecord) -> anyhow::Result<()> {
+        let mut line = serde_json::to_vec(record)?;
+        line.push(b'\n');
+        self.file.write_all(&line)?;
+
+        Ok(())
+    }
+}
+
+pub(crate) fn unix_millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_json() {
+        let record = EventRecord {
+            source: EventSource {
+                tx_hash: Some(H256::repeat_byte(1)),
+                block: Some(42),
+                log_index: Some(3),
+            },
+            program_id: Some("11111111111111111111111111111111".to_string()),
+            account_id: None,
+            payload: "0102".to_string(),
+            decision: Decision::DeadLettered,
+            signature: None,
+            error: Some("invalid data".to_string()),
+            dry_run: false,
+            received_at: 1_600_000_000_000,
+            queued_ms: None,
+            relay_ms: None,
+        };
+        let json: serde_json::Value = serde_json::to_value(&record).unwrap();
+
+        assert_eq!(json["TxHash"], format!("0x{}", "01".repeat(32)).as_str());
+        assert_eq!(json["Block"], 42);
+        assert_eq!(json["LogIndex"], 3);
+        assert_eq!(json["Decision"], "dead-lettered");
+        assert_eq!(json["Signature"], serde_json::Value::Null);
+    }
+}
@@ -0,0 +1,146 @@
+use ethers::types::Address;
+use serde::{de, Deserialize, Deserializer};
+use solana_sdk::pubkey::Pubkey;
+use std::str::FromStr;
+
+/// Matches relays by their target program, target account and the Ethereum contract that was
+/// called through `Moebius.execute` to produce the data. Fields that are not set match anything.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+pub struct Rule {
+    #[serde(rename = "ProgramId", default, deserialize_with = "deserialize_pubkey")]
+    pub program_id: Option<Pubkey>,
+
+    #[serde(rename = "AccountId", default, deserialize_with = "deserialize_pubkey")]
+    pub account_id: Option<Pubkey>,
+
+    #[serde(rename = "Target", default)]
+    pub target: Option<Address>,
+}
+
+impl Rule {
+    fn matches(&self, program_id: &Pubkey, account_id: &Pubkey, target: Option<&Address>) -> bool {
+        self.program_id.map_or(true, |id| id == *program_id)
+            && self.account_id.map_or(true, |id| id == *account_id)
+            && self.target.map_or(true, |t| target == Some(&t))
+    }
+}
+
+/// Decides which `MoebiusData` events the bridge relays. Deny rules always win. If there are
+/// allow rules, an event must match at least one of them, otherwise everything not denied is
+/// relayed.
+///
+/// A rule with a `Target` never matches an event whose target could not be decoded, e.g. when
+/// `Moebius.execute` was called by another contract rather than by the transaction itself.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct RelayFilter {
+    #[serde(rename = "Allow", default)]
+    pub allow: Vec<Rule>,
+
+    #[serde(rename = "Deny", default)]
+    pub deny: Vec<Rule>,
+}
+
+impl RelayFilter {
+    /// Whether any rule needs the `_target` of the `execute` call, which costs an extra RPC
+    /// request per event to fetch the transaction.
+    pub fn needs_target(&self) -> bool {
+        self.allow
+            .iter()
+            .chain(self.deny.iter())
+            .any(|rule| rule.target.is_some())
+    }
+
+    pub fn allows(
+        &self,
+        program_id: &Pubkey,
+        account_id: &Pubkey,
+        target: Option<&Address>,
+    ) -> bool {
+        if self
+            .deny
+            .iter()
+            .any(|rule| rule.matches(program_id, account_id, target))
+        {
+            return false;
+        }
+
+        self.allow.is_empty()
+            || self
+                .allow
+                .iter()
+                .any(|rule| rule.matches(program_id, account_id, target))
+    }
+}
+
+pub(crate) fn deserialize_pubkey<'de, D>(deserializer: D) -> Result<Option<Pubkey>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Option::<String>::deserialize(deserializer)?
+        .map(|s| Pubkey::from_str(&s).map_err(de::Error::custom))
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pubkey_rand() -> Pubkey {
+        Pubkey::new(&rand::random::<[u8; 32]>())
+    }
+
+    #[test]
+    fn test_empty_filter_allows_everything() {
+        let filter = RelayFilter::default();
+        assert!(filter.allows(&pubkey_rand(), &pubkey_rand(), None));
+    }
+
+    #[test]
+    fn test_allow_and_deny() {
+        let program_id = pubkey_rand();
+        let account_id = pubkey_rand();
+        let spam_account_id = pubkey_rand();
+        let target = Address::from_low_u64_be(1);
+
+        let filter = RelayFilter {
+            allow: vec![Rule {
+                program_id: Some(program_id),
+                target: Some(target),
+                ..Rule::default()
+            }],
+            deny: vec![Rule {
+                account_id: Some(spam_account_id),
+                ..Rule::default()
+            }],
+        };
+        assert!(filter.needs_target());
+
+        assert!(filter.allows(&program_id, &account_id, Some(&target)));
+        // unknown or other target contract.
+        assert!(!filter.allows(&program_id, &account_id, None));
+        assert!(!filter.allows(&program_id, &account_id, Some(&Address::zero())));
+        // other program.
+        assert!(!filter.allows(&pubkey_rand(), &account_id, Some(&target)));
+        // denied account, even though it is allowed.
+        assert!(!filter.allows(&program_id, &spam_account_id, Some(&target)));
+    }
+
+    #[test]
+    fn test_deserialize() {
+        let program_id = pubkey_rand();
+        let json = format!(
+            r#"{{
+                "Allow": [{{ "ProgramId": "{}", "Target": "0x4f2a9ac3a70400636190e1df213fd7aa0bcf794d" }}],
+                "Deny": [{{ "AccountId": "{}" }}]
+            }}"#,
+            program_id, program_id
+        );
+        let filter: RelayFilter = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(filter.allow.len(), 1);
+        assert_eq!(filter.allow[0].program_id, Some(program_id));
+        assert!(filter.allow[0].target.is_some());
+        assert_eq!(filter.deny[0].account_id, Some(program_id));
+        assert_eq!(filter.deny[0].program_id, None);
+    }
+}
@@ -0,0 +1,239 @@
+use anyhow::{anyhow, bail};
+use ethers::{
+    core::abi::{ParamType, Token},
+    prelude::*,
+};
+use serde::Deserialize;
+use std::{convert::TryInto, path::Path};
+
+/// The IDL the Moebius derive macros generate for a target program, as far as the bridge reads
+/// it: the layout of its state account.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct Idl {
+    #[serde(rename = "State")]
+    pub state: StateLayout,
+}
+
+impl Idl {
+    pub fn from_json(json: &str) -> anyhow::Result<Idl> {
+        serde_json::from_str(json).map_err(|e| anyhow!("Parsing IDL: {}", e))
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> anyhow::Result<Idl> {
+        let path = path.as_ref();
+        let json = std::fs::read_to_string(path)
+            .map_err(|e| anyhow!("Reading IDL {}: {}", path.display(), e))?;
+        Self::from_json(&json)
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct StateLayout {
+    #[serde(rename = "Name")]
+    pub name: String,
+
+    /// The size of the account, in bytes.
+    #[serde(rename = "Size")]
+    pub size: usize,
+
+    #[serde(rename = "Fields")]
+    pub fields: Vec<FieldLayout>,
+}
+
+impl StateLayout {
+    /// The fields relayed from Ethereum, in the order `UpdateState` carries them.
+    pub fn updated_fields(&self) -> impl Iterator<Item = &FieldLayout> {
+        self.fields
+            .iter()
+            .filter(|field| !matches!(field.name.as_str(), "is_initialized" | "authority"))
+            .filter(|field| !field.local && (!field.init_only || field.check_eq))
+    }
+
+    /// Decodes the field `name` of an account with this layout.
+    pub fn decode(&self, data: &[u8], name: &str) -> anyhow::Result<Token> {
+        if data.len() < self.size {
+            bail!(
+                "{} account of {} bytes, expected {}",
+                self.name,
+                data.len(),
+                self.size
+            );
+        }
+        self.fields
+            .iter()
+            .find(|field| field.name == name)
+            .ok_or_else(|| anyhow!("{} has no field {}", self.name, name))?
+            .decode(data)
+    }
+}
+
+/// A state field, at `offset` in the account.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct FieldLayout {
+    #[serde(rename = "Name")]
+    pub name: String,
+
+    /// The Solidity type, e.g. `uint256`.
+    #[serde(rename = "Type")]
+    pub ty: String,
+
+    #[serde(rename = "Offset")]
+    pub offset: usize,
+
+    #[serde(rename = "Size")]
+    pub size: usize,
+
+    #[serde(rename = "InitOnly", default)]
+    pub init_only: bool,
+
+    #[serde(rename = "Local", default)]
+    pub local: bool,
+
+    #[serde(rename = "CheckEq", default)]
+    pub check_eq: bool,
+}
+
+impl FieldLayout {
+    /// The ABI type of the field. Arrays are not supported.
+    pub fn param_type(&self) -> anyhow::Result<ParamType> {
+        let ty = self.ty.as_str();
+        let bits = |prefix: &str| ty[prefix.len()..].parse::<usize>().ok();
+        Ok(match ty {
+            "address" => ParamType::Address,
+            "bool" => ParamType::Bool,
+            "bytes" => ParamType::Bytes,
+            "string" => ParamType::String,
+            _ if ty.starts_with("uint") && bits("uint").is_some() => {
+                ParamType::Uint(bits("uint").unwrap())
+            }
+            _ if ty.starts_with("int") && bits("int").is_some() => {
+                ParamType::Int(bits("int").unwrap())
+            }
+            _ if ty.starts_with("bytes") && bits("bytes").is_some() => {
+                ParamType::FixedBytes(bits("bytes").unwrap())
+            }
+            _ => bail!("{}: unsupported type {}", self.name, ty),
+        })
+    }
+
+    /// Decodes the field from an account, as the value `UpdateState` carried.
+    pub fn decode(&self, data: &[u8]) -> anyhow::Result<Token> {
+        let src = data
+            .get(self.offset..self.offset + self.size)
+            .ok_or_else(|| anyhow!("{}: out of the account", self.name))?;
+
+        Ok(match self.param_type()? {
+            ParamType::Address => Token::Address(Address::from_slice(src)),
+            ParamType::Bool => Token::Bool(src[0] != 0),
+            ParamType::FixedBytes(_) => Token::FixedBytes(src.to_vec()),
+            // integers that don't fit a native integer are kept as the big-endian ABI word
+            ParamType::Uint(_) if self.size == 32 => Token::Uint(U256::from_big_endian(src)),
+            ParamType::Int(_) if self.size == 32 => Token::Int(U256::from_big_endian(src)),
+            ParamType::Uint(_) => Token::Uint(U256::from_little_endian(src)),
+            ParamType::Int(_) => {
+                // sign-extended to the two's complement ABI word
+                let negative = src[src.len() - 1] & 0x80 != 0;
+                let mut word = [if negative { 0xff } else { 0 }; 32];
+                for (dst, byte) in word.iter_mut().rev().zip(src) {
+                    *dst = *byte;
+                }
+                Token::Int(U256::from_big_endian(&word))
+            }
+            ParamType::Bytes => Token::Bytes(self.contents(src)?.to_vec()),
+            ParamType::String => Token::String(
+                String::from_utf8(self.contents(src)?.to_vec())
+                    .map_err(|e| anyhow!("{}: {}", self.name, e))?,
+            ),
+            ty => bail!("{}: unsupported type {:?}", self.name, ty),
+        })
+    }
+
+    /// The contents of a dynamic field, after its `u32` length.
+    fn contents<'a>(&self, src: &'a [u8]) -> anyhow::Result<&'a [u8]> {
+        let len = u32::from_le_bytes(src[..4].try_into().unwrap()) as usize;
+        src.get(4..4 + len)
+            .ok_or_else(|| anyhow!("{}: invalid length {}", self.name, len))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(name: &str, ty: &str, offset: usize, size: usize) -> FieldLayout {
+        FieldLayout {
+            name: name.to_string(),
+            ty: ty.to_string(),
+            offset,
+            size,
+            init_only: false,
+            local: false,
+            check_eq: false,
+        }
+    }
+
+    #[test]
+    fn test_decode_fields() {
+        let mut data = vec![0u8; 17];
+        data[..2].copy_from_slice(&18u16.to_le_bytes());
+        data[2] = 0xfe;
+        data[3..7].copy_from_slice(&3u32.to_le_bytes());
+        data[7..10].copy_from_slice(b"abc");
+        data[16] = 1;
+
+        assert_eq!(
+            field("decimals", "uint16", 0, 2).decode(&data).unwrap(),
+            Token::Uint(U256::from(18))
+        );
+        assert_eq!(
+            field("delta", "int8", 2, 1).decode(&data).unwrap(),
+            Token::Int(U256::max_value() - 1)
+        );
+        assert_eq!(
+            field("name", "string", 3, 12).decode(&data).unwrap(),
+            Token::String("abc".to_string())
+        );
+        assert_eq!(
+            field("active", "bool", 16, 1).decode(&data).unwrap(),
+            Token::Bool(true)
+        );
+        assert!(field("active", "bool", 17, 1).decode(&data).is_err());
+        assert!(field("prices", "uint256[2]", 0, 64).param_type().is_err());
+    }
+
+    #[test]
+    fn test_updated_fields() {
+        let idl = Idl::from_json(
+            r#"{
+              "State": {
+                "Name": "OracleState",
+                "Size": 94,
+                "Fields": [
+                  { "Name": "is_initialized", "Type": "bool", "Offset": 0, "Size": 1 },
+                  { "Name": "authority", "Type": "pubkey", "Offset": 1, "Size": 32 },
+                  { "Name": "token", "Type": "address", "Offset": 33, "Size": 20,
+                    "InitOnly": true, "CheckEq": true },
+                  { "Name": "decimals", "Type": "uint8", "Offset": 53, "Size": 1,
+                    "InitOnly": true },
+                  { "Name": "amount", "Type": "uint256", "Offset": 54, "Size": 32 },
+                  { "Name": "slot", "Type": "uint64", "Offset": 86, "Size": 8, "Local": true }
+                ]
+              },
+              "Instruction": {}
+            }"#,
+        )
+        .unwrap();
+
+        let names: Vec<_> = idl
+            .state
+            .updated_fields()
+            .map(|field| field.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["token", "amount"]);
+        assert!(idl.state.decode(&[0u8; 93], "amount").is_err());
+        assert_eq!(
+            idl.state.decode(&[0u8; 94], "amount").unwrap(),
+            Token::Uint(U256::zero())
+        );
+    }
+}
@@ -0,0 +1,258 @@
+use fs2::FileExt;
+use futures::future::{self, AbortHandle};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{File, OpenOptions},
+    future::Future,
+    io::{Read, Seek, SeekFrom, Write},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+/// A lease that at most one bridge replica holds at a time. Implementations must make
+/// `try_acquire` atomic across all the replicas sharing the lease.
+pub trait LeaderLease: Send + Sync {
+    /// Takes the lease for `holder` if it is free or expired, or extends it if `holder` already
+    /// holds it. Returns whether `holder` holds the lease for the next `ttl`.
+    fn try_acquire(&self, holder: &str, ttl: Duration) -> anyhow::Result<bool>;
+
+    /// Gives up the lease if `holder` holds it, so that another replica can take over right away.
+    fn release(&self, holder: &str) -> anyhow::Result<()>;
+}
+
+#[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
+struct LeaseRecord {
+    #[serde(rename = "Holder")]
+    holder: String,
+
+    /// Milliseconds since the unix epoch.
+    #[serde(rename = "ExpiresAt")]
+    expires_at: u64,
+}
+
+/// A lease kept in a file that all replicas can access, e.g. on a shared volume. Every access
+/// takes an exclusive lock on the file. Expiry relies on the replicas' clocks being in sync.
+pub struct FileLease {
+    path: PathBuf,
+}
+
+impl FileLease {
+    pub fn new<P: Into<PathBuf>>(path: P) -> FileLease {
+        Self { path: path.into() }
+    }
+
+    /// Runs `f` on the lease record while holding the file lock, and writes the record back if
+    /// `f` changed it.
+    fn update<T, F>(&self, f: F) -> anyhow::Result<T>
+    where
+        F: FnOnce(&mut LeaseRecord) -> T,
+    {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&self.path)?;
+        file.lock_exclusive()?;
+
+        let result = read_and_update(&mut file, f);
+        file.unlock()?;
+
+        result
+    }
+}
+
+fn read_and_update<T, F>(file: &mut File, f: F) -> anyhow::Result<T>
+where
+    F: FnOnce(&mut LeaseRecord) -> T,
+{
+    let mut contents = String::new();
+    file.read_to_string(&mut contents)?;
+    let record = if contents.trim().is_empty() {
+        LeaseRecord::default()
+    } else {
+        serde_json::from_str(&contents)?
+    };
+
+    let mut updated = record;
+    let result = f(&mut updated);
+    if serde_json::to_string(&updated)? != contents {
+        file.set_len(0)?;
+        file.seek(SeekFrom::Start(0))?;
+        file.write_all(serde_json::to_string(&updated)?.as_bytes())?;
+        file.sync_all()?;
+    }
+
+    Ok(result)
+}
+
+impl LeaderLease for FileLease {
+    fn try_acquire(&self, holder: &str, ttl: Duration) -> anyhow::Result<bool> {
+        let now = unix_millis();
+        self.update(|record| {
+            if record.holder != holder && record.expires_at > now {
+                return false;
+            }
+            record.holder = holder.to_string();
+            record.expires_at = now + ttl.as_millis() as u64;
+            true
+        })
+    }
+
+    fn release(&self, holder: &str) -> anyhow::Result<()> {
+        self.update(|record| {
+            if record.holder == holder {
+                record.expires_at = 0;
+            }
+        })
+    }
+}
+
+fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as u64)
+}
+
+/// Acquires and renews a leader lease for this replica. The lease is renewed every third of
+/// its ttl, and considered lost one renewal interval before it would expire, so that the
+/// replica stops relaying before any other one can take over.
+#[derive(Clone)]
+pub struct Leadership {
+    lease: Arc<dyn LeaderLease>,
+    holder: String,
+    ttl: Duration,
+    held: Arc<AtomicBool>,
+}
+
+impl Leadership {
+    pub fn new(lease: Arc<dyn LeaderLease>, holder: String, ttl: Duration) -> Leadership {
+        Self {
+            lease,
+            holder,
+            ttl,
+            held: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    fn renew_interval(&self) -> Duration {
+        self.ttl / 3
+    }
+
+    /// Whether this replica held the lease when it was last acquired or renewed.
+    pub fn is_held(&self) -> bool {
+        self.held.load(Ordering::SeqCst)
+    }
+
+    async fn try_acquire(&self) -> anyhow::Result<bool> {
+        let lease = Arc::clone(&self.lease);
+        let holder = self.holder.clone();
+        let ttl = self.ttl;
+        let acquired =
+            tokio::task::spawn_blocking(move || lease.try_acquire(&holder, ttl)).await??;
+        self.held.store(acquired, Ordering::SeqCst);
+
+        Ok(acquired)
+    }
+
+    /// Waits until this replica holds the lease.
+    pub async fn acquire(&self) -> anyhow::Result<()> {
+        while !self.try_acquire().await? {
+            tokio::time::delay_for(self.renew_interval()).await;
+        }
+        info!("Acquired leader lease as {}", self.holder);
+
+        Ok(())
+    }
+
+    /// Renews the lease in a task of its own, so that the renewals never wait on the caller.
+    /// The returned future completes once the lease is lost, and dropping it stops the renewals.
+    pub fn keep_renewing(&self) -> impl Future<Output = ()> + Send + 'static {
+        let leadership = self.clone();
+        let (lost, abort) = future::abortable(async move { leadership.lost().await });
+        let renewals = tokio::spawn(lost);
+        let guard = AbortOnDrop(abort);
+
+        async move {
+            let _guard = guard;
+            let _ = renewals.await;
+        }
+    }
+
+    /// Renews the lease, and completes once it is lost.
+    async fn lost(&self) {
+        let mut renewed_at = Instant::now();
+        loop {
+            tokio::time::delay_for(self.renew_interval()).await;
+
+            match self.try_acquire().await {
+                Ok(true) => renewed_at = Instant::now(),
+                Ok(false) => {
+                    warn!("Leader lease was taken over by another replica");
+                    return;
+                }
+                Err(e) => {
+                    warn!("Renewing leader lease: {}", e);
+                    if renewed_at.elapsed() + self.renew_interval() >= self.ttl {
+                        warn!("Could not renew leader lease in time");
+                        self.held.store(false, Ordering::SeqCst);
+                        return;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Gives up the lease, if this replica still holds it.
+    pub async fn release(&self) -> anyhow::Result<()> {
+        let lease = Arc::clone(&self.lease);
+        let holder = self.holder.clone();
+        tokio::task::spawn_blocking(move || lease.release(&holder)).await??;
+        self.held.store(false, Ordering::SeqCst);
+
+        Ok(())
+    }
+}
+
+/// Aborts the task when dropped.
+struct AbortOnDrop(AbortHandle);
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_lease() {
+        let path = std::env::temp_dir().join(format!("moebius-lease-{}.json", std::process::id()));
+        let lease = FileLease::new(&path);
+        let ttl = Duration::from_secs(60);
+
+        assert!(lease.try_acquire("a", ttl).unwrap());
+        assert!(!lease.try_acquire("b", ttl).unwrap());
+        // renewing.
+        assert!(lease.try_acquire("a", ttl).unwrap());
+
+        // releasing only works for the holder.
+        lease.release("b").unwrap();
+        assert!(!lease.try_acquire("b", ttl).unwrap());
+        lease.release("a").unwrap();
+        assert!(lease.try_acquire("b", ttl).unwrap());
+
+        // an expired lease can be taken over.
+        assert!(lease.try_acquire("b", Duration::from_secs(0)).unwrap());
+        assert!(lease.try_acquire("a", ttl).unwrap());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
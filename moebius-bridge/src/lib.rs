@@ -1,6 +1,34 @@
+mod balance;
 mod bindings;
 mod broadcaster;
+mod checkpoint;
+mod config;
+mod event_log;
+mod filter;
+mod idl;
+mod lease;
+#[cfg(feature = "test-util")]
+mod memory;
+mod reconcile;
+mod rpc;
+mod signer;
+mod sink;
+mod throttle;
 mod watcher;
 
-pub use broadcaster::Broadcaster;
+pub use balance::{BalanceLimits, BalanceMonitor};
+pub use broadcaster::{instruction_update_data, Broadcaster, RpcBroadcaster};
+pub use checkpoint::{Checkpoint, PendingRelay};
+pub use config::{profile_path, Config, ConfigLayer, DEFAULT_PROFILE, ENV_PREFIX};
+pub use event_log::{Decision, EventLog, EventRecord, EventSource};
+pub use filter::{RelayFilter, Rule};
+pub use idl::{FieldLayout, Idl, StateLayout};
+pub use lease::{FileLease, LeaderLease, Leadership};
+#[cfg(feature = "test-util")]
+pub use memory::MemoryBroadcaster;
+pub use reconcile::{Drift, Integration, IntegrationKind, Reconciler, Report};
+pub use rpc::SolanaRpcClient;
+pub use signer::{RemoteSigner, SharedSigner, SignerServer};
+pub use sink::{JsonlBroadcaster, RecordedUpdate, SinkKind, StdoutBroadcaster};
+pub use throttle::{RateLimits, TargetRateLimit, Throttle};
 pub use watcher::MoebiusWatcher;
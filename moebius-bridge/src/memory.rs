@@ -0,0 +1,79 @@
+use crate::{
+    broadcaster::{instruction_update_data, Broadcaster},
+    sink::simulated,
+};
+use async_trait::async_trait;
+use solana_client::rpc_response::RpcSimulateTransactionResult;
+use solana_sdk::{instruction::Instruction, pubkey::Pubkey, signature::Signature};
+use std::sync::{Arc, Mutex};
+
+/// Keeps the instructions that would have been sent or simulated, for tests. Clones share the
+/// recorded instructions.
+#[derive(Clone)]
+pub struct MemoryBroadcaster {
+    moebius_account: Pubkey,
+    authority: Pubkey,
+    sent: Arc<Mutex<Vec<Instruction>>>,
+    simulated: Arc<Mutex<Vec<Instruction>>>,
+}
+
+impl MemoryBroadcaster {
+    pub fn new(moebius_account: Pubkey, authority: Pubkey) -> MemoryBroadcaster {
+        Self {
+            moebius_account,
+            authority,
+            sent: Arc::new(Mutex::new(Vec::new())),
+            simulated: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    pub fn sent(&self) -> Vec<Instruction> {
+        self.sent.lock().unwrap().clone()
+    }
+
+    pub fn simulated(&self) -> Vec<Instruction> {
+        self.simulated.lock().unwrap().clone()
+    }
+
+    fn instructions(
+        &self,
+        program_id: [u8; 32],
+        account_id: [u8; 32],
+        data: Vec<u8>,
+    ) -> anyhow::Result<Vec<Instruction>> {
+        instruction_update_data(
+            &self.moebius_account,
+            &self.authority,
+            &Pubkey::new_from_array(program_id),
+            &Pubkey::new_from_array(account_id),
+            data,
+        )
+    }
+}
+
+#[async_trait]
+impl Broadcaster for MemoryBroadcaster {
+    async fn broadcast(
+        &self,
+        program_id: [u8; 32],
+        account_id: [u8; 32],
+        data: Vec<u8>,
+    ) -> anyhow::Result<Option<Signature>> {
+        let instructions = self.instructions(program_id, account_id, data)?;
+        self.sent.lock().unwrap().extend(instructions);
+
+        Ok(None)
+    }
+
+    async fn simulate(
+        &self,
+        program_id: [u8; 32],
+        account_id: [u8; 32],
+        data: Vec<u8>,
+    ) -> anyhow::Result<RpcSimulateTransactionResult> {
+        let instructions = self.instructions(program_id, account_id, data)?;
+        self.simulated.lock().unwrap().extend(instructions);
+
+        Ok(simulated())
+    }
+}
@@ -0,0 +1,501 @@
+use crate::{
+    bindings::{SimpleContract, UniswapOracle},
+    idl::{Idl, StateLayout},
+};
+use anyhow::anyhow;
+use ethers::{
+    core::abi::{self, Token},
+    prelude::*,
+};
+use rustc_hex::ToHex;
+use serde::Deserialize;
+use simple_program::state::SimpleProgram;
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+use std::{
+    fmt,
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use uniswap_program::state::UniswapOracle as UniswapOracleState;
+
+/// Which contract an integration reads from.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+pub enum IntegrationKind {
+    /// A contract whose `getValues()` returns the program, the account and the `_packedData`
+    /// of its last update, like `SimpleContract`.
+    SimpleContract,
+    /// `UniswapOracle`, relayed to an account with `token0`, `token1`, `amount0` and `amount1`
+    /// fields.
+    UniswapOracle,
+}
+
+impl IntegrationKind {
+    /// The IDL of the in-tree program the contract relays to.
+    fn default_idl(self) -> &'static str {
+        match self {
+            IntegrationKind::SimpleContract => SimpleProgram::IDL,
+            IntegrationKind::UniswapOracle => UniswapOracleState::IDL,
+        }
+    }
+}
+
+/// An Ethereum contract whose values are relayed to a Solana account.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Integration {
+    #[serde(rename = "Name")]
+    pub name: String,
+
+    #[serde(rename = "Kind")]
+    pub kind: IntegrationKind,
+
+    /// Address of the contract on Ethereum. The Solana program and account are read from it.
+    #[serde(rename = "Contract")]
+    pub contract: Address,
+
+    /// Report the integration as stale when either side was last updated longer ago than this.
+    #[serde(rename = "MaxAgeSecs", default)]
+    pub max_age_secs: Option<u64>,
+
+    /// Relative difference tolerated between the amounts of a `UniswapOracle`, e.g. 0.01 for
+    /// 1%, as prices move between updates.
+    #[serde(rename = "Tolerance", default)]
+    pub tolerance: f64,
+
+    /// The IDL of the Solana program, which lays out its accounts. Defaults to the IDL of the
+    /// in-tree program for the kind.
+    #[serde(rename = "Idl", default)]
+    pub idl: Option<PathBuf>,
+}
+
+impl Integration {
+    pub fn idl(&self) -> anyhow::Result<Idl> {
+        match &self.idl {
+            Some(path) => Idl::from_file(path),
+            None => Idl::from_json(self.kind.default_idl()),
+        }
+    }
+}
+
+/// A value that differs between Ethereum and Solana.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Drift {
+    pub field: String,
+    pub ethereum: String,
+    pub solana: String,
+}
+
+/// The outcome of reconciling one integration.
+#[derive(Clone, Debug)]
+pub struct Report {
+    pub name: String,
+    pub account: Pubkey,
+    pub drift: Vec<Drift>,
+    /// How long ago the Solana account was last written to, if it ever was.
+    pub solana_age: Option<Duration>,
+    /// How long ago the Ethereum source was last updated, for sources that keep track of it.
+    pub source_age: Option<Duration>,
+    pub max_age: Option<Duration>,
+}
+
+impl Report {
+    pub fn is_stale(&self) -> bool {
+        let max_age = match self.max_age {
+            Some(max_age) => max_age,
+            None => return false,
+        };
+
+        self.solana_age.map_or(true, |age| age > max_age)
+            || self.source_age.map_or(false, |age| age > max_age)
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.drift.is_empty() && !self.is_stale()
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let status = match (self.drift.is_empty(), self.is_stale()) {
+            (true, false) => "ok",
+            (true, true) => "stale",
+            (false, false) => "drifted",
+            (false, true) => "drifted, stale",
+        };
+        write!(f, "{} ({}): {}", self.name, self.account, status)?;
+
+        for drift in self.drift.iter() {
+            write!(
+                f,
+                "\n  {}: {} on Ethereum, {} on Solana",
+                drift.field, drift.ethereum, drift.solana
+            )?;
+        }
+        match self.solana_age {
+            Some(age) => write!(f, "\n  Solana account updated {}s ago", age.as_secs())?,
+            None => write!(f, "\n  Solana account never updated")?,
+        }
+        if let Some(age) = self.source_age {
+            write!(f, "\n  Ethereum source updated {}s ago", age.as_secs())?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Compares the values of Ethereum contracts with the Solana accounts they are relayed to.
+pub struct Reconciler<M> {
+    client: Arc<M>,
+    rpc_client: Arc<RpcClient>,
+}
+
+impl<M: Middleware + 'static> Reconciler<M> {
+    pub fn new(client: Arc<M>, solana_rpc_url: String) -> Reconciler<M> {
+        Self {
+            client,
+            rpc_client: Arc::new(RpcClient::new(solana_rpc_url)),
+        }
+    }
+
+    pub async fn reconcile(&self, integration: &Integration) -> anyhow::Result<Report> {
+        let layout = integration.idl()?.state;
+        let (account, drift, source_age) = match integration.kind {
+            IntegrationKind::SimpleContract => self.reconcile_simple(integration, &layout).await?,
+            IntegrationKind::UniswapOracle => self.reconcile_uniswap(integration, &layout).await?,
+        };
+
+        let rpc_client = Arc::clone(&self.rpc_client);
+        let solana_age =
+            tokio::task::spawn_blocking(move || last_update_age(&rpc_client, &account)).await??;
+
+        Ok(Report {
+            name: integration.name.clone(),
+            account,
+            drift,
+            solana_age,
+            source_age,
+            max_age: integration.max_age_secs.map(Duration::from_secs),
+        })
+    }
+
+    async fn reconcile_simple(
+        &self,
+        integration: &Integration,
+        layout: &StateLayout,
+    ) -> anyhow::Result<(Pubkey, Vec<Drift>, Option<Duration>)> {
+        let contract = SimpleContract::new(integration.contract, Arc::clone(&self.client));
+        let (program_id, account_id, packed_data) = contract.get_values().call().await?;
+        let account = Pubkey::new_from_array(account_id);
+
+        let (owner, data) = self.account(account).await?;
+        let mut drift = Vec::new();
+        check(
+            &mut drift,
+            "ProgramId",
+            Pubkey::new_from_array(program_id),
+            owner,
+        );
+        drift.extend(compare_simple(&packed_data, layout, &data)?);
+
+        Ok((account, drift, None))
+    }
+
+    async fn reconcile_uniswap(
+        &self,
+        integration: &Integration,
+        layout: &StateLayout,
+    ) -> anyhow::Result<(Pubkey, Vec<Drift>, Option<Duration>)> {
+        let contract = UniswapOracle::new(integration.contract, Arc::clone(&self.client));
+        let program_id = contract.program_id().call().await?;
+        let account = Pubkey::new_from_array(contract.account_id().call().await?);
+        let token0 = contract.token_0().call().await?;
+        let token1 = contract.token_1().call().await?;
+        let updated_at = contract.block_timestamp_last().call().await?;
+
+        let (owner, data) = self.account(account).await?;
+        // The relayed amount1 is what the oracle quoted for the relayed amount0, so quoting the
+        // same amount0 again shows how far the price has moved since.
+        let amount0 = uint(layout, &data, "amount0")?;
+        let amount1 = contract.consult(token0, amount0).call().await?;
+        let mut drift = Vec::new();
+        check(
+            &mut drift,
+            "ProgramId",
+            Pubkey::new_from_array(program_id),
+            owner,
+        );
+        drift.extend(compare_uniswap(
+            token0,
+            token1,
+            amount1,
+            layout,
+            &data,
+            integration.tolerance,
+        )?);
+
+        let source_age = age(UNIX_EPOCH + Duration::from_secs(updated_at as u64));
+
+        Ok((account, drift, Some(source_age)))
+    }
+
+    /// Fetches the data of an account, along with the program owning it.
+    async fn account(&self, account: Pubkey) -> anyhow::Result<(Pubkey, Vec<u8>)> {
+        let rpc_client = Arc::clone(&self.rpc_client);
+        tokio::task::spawn_blocking(move || -> anyhow::Result<(Pubkey, Vec<u8>)> {
+            let info = rpc_client.get_account(&account)?;
+            Ok((info.owner, info.data))
+        })
+        .await?
+    }
+}
+
+/// How long ago the most recent transaction touching the account was confirmed.
+fn last_update_age(rpc_client: &RpcClient, account: &Pubkey) -> anyhow::Result<Option<Duration>> {
+    let signatures = rpc_client.get_confirmed_signatures_for_address2(account)?;
+    let slot = match signatures.first() {
+        Some(status) => status.slot,
+        None => return Ok(None),
+    };
+    let block_time = rpc_client.get_block_time(slot)?;
+
+    Ok(Some(age(
+        UNIX_EPOCH + Duration::from_secs(block_time as u64)
+    )))
+}
+
+fn age(time: SystemTime) -> Duration {
+    SystemTime::now().duration_since(time).unwrap_or_default()
+}
+
+/// Compares the values `SimpleContract.getValues()` packs with the fields of the account
+/// `UpdateState` wrote them to.
+fn compare_simple(
+    packed_data: &[u8],
+    layout: &StateLayout,
+    data: &[u8],
+) -> anyhow::Result<Vec<Drift>> {
+    let fields: Vec<_> = layout.updated_fields().collect();
+    let param_types = fields
+        .iter()
+        .map(|field| field.param_type())
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let tokens = abi::decode(&param_types, packed_data)?;
+
+    let mut drift = Vec::new();
+    for (field, ethereum) in fields.into_iter().zip(tokens) {
+        let solana = layout.decode(data, &field.name)?;
+        if ethereum != solana {
+            drift.push(Drift {
+                field: pascal_case(&field.name),
+                ethereum: token_string(&ethereum),
+                solana: token_string(&solana),
+            });
+        }
+    }
+
+    Ok(drift)
+}
+
+/// Compares a `UniswapOracle` account with the oracle's tokens, and its current quote for the
+/// account's `amount0`.
+fn compare_uniswap(
+    token0: Address,
+    token1: Address,
+    amount1: U256,
+    layout: &StateLayout,
+    data: &[u8],
+    tolerance: f64,
+) -> anyhow::Result<Vec<Drift>> {
+    let mut drift = Vec::new();
+    check(
+        &mut drift,
+        "Token0",
+        token0,
+        address(layout, data, "token0")?,
+    );
+    check(
+        &mut drift,
+        "Token1",
+        token1,
+        address(layout, data, "token1")?,
+    );
+
+    let relayed_amount1 = uint(layout, data, "amount1")?;
+    if relative_difference(amount1, relayed_amount1) > tolerance {
+        drift.push(Drift {
+            field: "Amount1".to_string(),
+            ethereum: amount1.to_string(),
+            solana: relayed_amount1.to_string(),
+        });
+    }
+
+    Ok(drift)
+}
+
+fn address(layout: &StateLayout, data: &[u8], name: &str) -> anyhow::Result<Address> {
+    layout
+        .decode(data, name)?
+        .into_address()
+        .ok_or_else(|| anyhow!("{}.{} is not an address", layout.name, name))
+}
+
+fn uint(layout: &StateLayout, data: &[u8], name: &str) -> anyhow::Result<U256> {
+    layout
+        .decode(data, name)?
+        .into_uint()
+        .ok_or_else(|| anyhow!("{}.{} is not an unsigned integer", layout.name, name))
+}
+
+/// `val_uint256` is reported as `ValUint256`.
+fn pascal_case(name: &str) -> String {
+    name.split('_')
+        .map(|word| {
+            let mut chars = word.chars();
+            chars
+                .next()
+                .map_or_else(String::new, |c| c.to_uppercase().chain(chars).collect())
+        })
+        .collect()
+}
+
+fn token_string(token: &Token) -> String {
+    match token {
+        Token::Address(address) => format!("{:?}", address),
+        Token::FixedBytes(bytes) | Token::Bytes(bytes) => {
+            format!("0x{}", bytes.to_hex::<String>())
+        }
+        Token::Uint(value) | Token::Int(value) => value.to_string(),
+        Token::Bool(value) => value.to_string(),
+        Token::String(value) => value.clone(),
+        token => format!("{:?}", token),
+    }
+}
+
+fn check<T: PartialEq + fmt::Debug>(drift: &mut Vec<Drift>, field: &str, ethereum: T, solana: T) {
+    if ethereum != solana {
+        drift.push(Drift {
+            field: field.to_string(),
+            ethereum: format!("{:?}", ethereum),
+            solana: format!("{:?}", solana),
+        });
+    }
+}
+
+/// `|a - b| / max(a, b)`, or 0 if both are 0.
+fn relative_difference(a: U256, b: U256) -> f64 {
+    let (larger, smaller) = if a > b { (a, b) } else { (b, a) };
+    if larger.is_zero() {
+        return 0.0;
+    }
+
+    // Scale both down so that they fit in a u128 without losing the relevant digits.
+    let shift = larger.bits().saturating_sub(128);
+    let larger = (larger >> shift).as_u128() as f64;
+    let smaller = (smaller >> shift).as_u128() as f64;
+
+    (larger - smaller) / larger
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::program_pack::Pack;
+
+    fn pack<T: Pack>(state: T) -> Vec<u8> {
+        let mut data = vec![0u8; T::LEN];
+        T::pack(state, &mut data).unwrap();
+        data
+    }
+
+    #[test]
+    fn test_compare_simple() {
+        let mut state = SimpleProgram {
+            is_initialized: true,
+            authority: Pubkey::default(),
+            val_bytes32: [1u8; 32],
+            val_address: [2u8; 20],
+            val_uint256: [0u8; 32],
+        };
+        state.val_uint256[31] = 42;
+        let packed_data = abi::encode(&[
+            Token::FixedBytes(vec![1u8; 32]),
+            Token::Address(Address::repeat_byte(2)),
+            Token::Uint(U256::from(42)),
+        ]);
+        let layout = Idl::from_json(SimpleProgram::IDL).unwrap().state;
+        assert_eq!(
+            compare_simple(&packed_data, &layout, &pack(state)).unwrap(),
+            vec![]
+        );
+
+        state.val_uint256[31] = 43;
+        let drift = compare_simple(&packed_data, &layout, &pack(state)).unwrap();
+        assert_eq!(drift.len(), 1);
+        assert_eq!(drift[0].field, "ValUint256");
+        assert_eq!(drift[0].ethereum, "42");
+        assert_eq!(drift[0].solana, "43");
+
+        assert!(compare_simple(&[0u8; 10], &layout, &pack(state)).is_err());
+        assert!(compare_simple(&packed_data, &layout, &[0u8; 10]).is_err());
+    }
+
+    #[test]
+    fn test_compare_uniswap() {
+        let mut state = UniswapOracleState {
+            is_initialized: true,
+            authority: Pubkey::default(),
+            token0: [1u8; 20],
+            decimal0: 18,
+            amount0: [0u8; 32],
+            token1: [2u8; 20],
+            decimal1: 6,
+            amount1: [0u8; 32],
+        };
+        U256::from(1000).to_big_endian(&mut state.amount1);
+        let layout = Idl::from_json(UniswapOracleState::IDL).unwrap().state;
+        let data = pack(state);
+        let token0 = Address::repeat_byte(1);
+        let token1 = Address::repeat_byte(2);
+
+        let compare = |token0, amount1, tolerance| {
+            compare_uniswap(token0, token1, amount1, &layout, &data, tolerance).unwrap()
+        };
+        assert!(compare(token0, U256::from(1000), 0.0).is_empty());
+        // within 1%.
+        assert!(compare(token0, U256::from(1005), 0.01).is_empty());
+        let drift = compare(token1, U256::from(1100), 0.01);
+        assert_eq!(
+            drift.iter().map(|d| d.field.as_str()).collect::<Vec<_>>(),
+            vec!["Token0", "Amount1"]
+        );
+
+        assert_eq!(relative_difference(U256::zero(), U256::zero()) as u64, 0);
+        assert_eq!(
+            (relative_difference(U256::max_value(), U256::max_value() / 2) * 100.0).round() as u64,
+            50
+        );
+    }
+
+    #[test]
+    fn test_report_staleness() {
+        let mut report = Report {
+            name: "simple".to_string(),
+            account: Pubkey::default(),
+            drift: vec![],
+            solana_age: Some(Duration::from_secs(30)),
+            source_age: None,
+            max_age: None,
+        };
+        assert!(report.is_ok());
+
+        report.max_age = Some(Duration::from_secs(60));
+        assert!(report.is_ok());
+        report.source_age = Some(Duration::from_secs(90));
+        assert!(report.is_stale());
+        report.source_age = None;
+        report.solana_age = None;
+        assert!(report.is_stale());
+    }
+}
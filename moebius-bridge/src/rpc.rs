@@ -0,0 +1,133 @@
+use anyhow::anyhow;
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::{json, Value};
+use solana_client::{
+    rpc_config::{RpcSendTransactionConfig, RpcSimulateTransactionConfig},
+    rpc_response::{Response, RpcBlockhashFeeCalculator, RpcSimulateTransactionResult},
+};
+use solana_sdk::{
+    commitment_config::CommitmentConfig, fee_calculator::FeeCalculator, hash::Hash, pubkey::Pubkey,
+    signature::Signature, transaction::Transaction,
+};
+use std::{
+    str::FromStr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+/// An async client for the few Solana JSON-RPC methods the bridge calls. Clones share the same
+/// HTTP connection pool.
+#[derive(Clone)]
+pub struct SolanaRpcClient {
+    url: String,
+    http: reqwest::Client,
+    next_id: Arc<AtomicU64>,
+}
+
+#[derive(Deserialize)]
+struct RpcResponse<T> {
+    result: Option<T>,
+    error: Option<RpcErrorObject>,
+}
+
+#[derive(Deserialize)]
+struct RpcErrorObject {
+    code: i64,
+    message: String,
+}
+
+impl SolanaRpcClient {
+    pub fn new(url: String) -> SolanaRpcClient {
+        Self {
+            url,
+            http: reqwest::Client::new(),
+            next_id: Arc::new(AtomicU64::new(1)),
+        }
+    }
+
+    async fn request<T: DeserializeOwned>(&self, method: &str, params: Value) -> anyhow::Result<T> {
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": self.next_id.fetch_add(1, Ordering::Relaxed),
+            "method": method,
+            "params": params,
+        });
+        let response: RpcResponse<T> = self
+            .http
+            .post(&self.url)
+            .json(&request)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        match (response.result, response.error) {
+            (_, Some(e)) => Err(anyhow!("{} failed ({}): {}", method, e.code, e.message)),
+            (Some(result), None) => Ok(result),
+            (None, None) => Err(anyhow!("{} returned no result", method)),
+        }
+    }
+
+    pub async fn get_recent_blockhash(
+        &self,
+        commitment: CommitmentConfig,
+    ) -> anyhow::Result<(Hash, FeeCalculator)> {
+        let response: Response<RpcBlockhashFeeCalculator> = self
+            .request("getRecentBlockhash", json!([commitment]))
+            .await?;
+        let blockhash = Hash::from_str(&response.value.blockhash)
+            .map_err(|e| anyhow!("Invalid blockhash: {}", e))?;
+
+        Ok((blockhash, response.value.fee_calculator))
+    }
+
+    pub async fn get_balance(&self, account: &Pubkey) -> anyhow::Result<u64> {
+        let response: Response<u64> = self
+            .request("getBalance", json!([account.to_string()]))
+            .await?;
+
+        Ok(response.value)
+    }
+
+    pub async fn send_transaction(
+        &self,
+        transaction: &Transaction,
+        config: RpcSendTransactionConfig,
+    ) -> anyhow::Result<Signature> {
+        let signature: String = self
+            .request(
+                "sendTransaction",
+                json!([encode_transaction(transaction)?, config]),
+            )
+            .await?;
+
+        Signature::from_str(&signature).map_err(|e| anyhow!("Invalid signature: {}", e))
+    }
+
+    pub async fn simulate_transaction(
+        &self,
+        transaction: &Transaction,
+        sig_verify: bool,
+    ) -> anyhow::Result<RpcSimulateTransactionResult> {
+        let config = RpcSimulateTransactionConfig {
+            sig_verify,
+            ..RpcSimulateTransactionConfig::default()
+        };
+        let response: Response<RpcSimulateTransactionResult> = self
+            .request(
+                "simulateTransaction",
+                json!([encode_transaction(transaction)?, config]),
+            )
+            .await?;
+
+        Ok(response.value)
+    }
+}
+
+/// Serializes the transaction as base58, the encoding the RPC methods default to.
+fn encode_transaction(transaction: &Transaction) -> anyhow::Result<String> {
+    Ok(bs58::encode(bincode::serialize(transaction)?).into_string())
+}
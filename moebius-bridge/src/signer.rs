@@ -0,0 +1,325 @@
+use anyhow::{anyhow, bail};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use solana_sdk::{
+    message::Message,
+    pubkey::Pubkey,
+    signature::{Keypair, Signature, Signer, SignerError},
+};
+use std::{
+    convert::TryInto,
+    io::{BufRead, BufReader, Write},
+    os::unix::net::{UnixListener, UnixStream},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+};
+
+/// A signer that can be shared between the broadcaster and its blocking tasks.
+pub type SharedSigner = Arc<dyn Signer + Send + Sync>;
+
+/// Requests understood by a signing service. Every request and response is a single line of
+/// JSON on a Unix socket.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(tag = "method", rename_all = "snake_case")]
+enum SignerRequest {
+    Pubkey,
+    Sign { message: String },
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct SignerResponse {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pubkey: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    signature: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// Signer that forwards signing requests to a separate process over a Unix socket, so that the
+/// Moebius authority key never has to be loaded by the bridge.
+pub struct RemoteSigner {
+    socket: PathBuf,
+    pubkey: Pubkey,
+}
+
+impl RemoteSigner {
+    /// Connects to the signing service and fetches the public key it signs for.
+    pub fn connect<P: AsRef<Path>>(socket: P) -> anyhow::Result<RemoteSigner> {
+        let socket = socket.as_ref().to_path_buf();
+        let response = request(&socket, &SignerRequest::Pubkey)?;
+        let pubkey = response
+            .pubkey
+            .ok_or_else(|| anyhow!("Remote signer did not return a pubkey"))?;
+
+        Ok(Self {
+            socket,
+            pubkey: Pubkey::from_str(&pubkey)?,
+        })
+    }
+}
+
+impl Signer for RemoteSigner {
+    fn try_pubkey(&self) -> Result<Pubkey, SignerError> {
+        Ok(self.pubkey)
+    }
+
+    fn try_sign_message(&self, message: &[u8]) -> Result<Signature, SignerError> {
+        let response = request(
+            &self.socket,
+            &SignerRequest::Sign {
+                message: bs58::encode(message).into_string(),
+            },
+        )
+        .map_err(|e| SignerError::Connection(e.to_string()))?;
+        let signature = response
+            .signature
+            .ok_or_else(|| SignerError::Protocol("missing signature".to_string()))?;
+        let signature =
+            Signature::from_str(&signature).map_err(|e| SignerError::Protocol(e.to_string()))?;
+
+        // Never trust the service blindly, the signature must be valid for our pubkey.
+        if !signature.verify(self.pubkey.as_ref(), message) {
+            return Err(SignerError::Protocol("invalid signature".to_string()));
+        }
+
+        Ok(signature)
+    }
+}
+
+fn request(socket: &Path, request: &SignerRequest) -> anyhow::Result<SignerResponse> {
+    let mut stream = UnixStream::connect(socket)?;
+    let mut line = serde_json::to_string(request)?;
+    line.push('\n');
+    stream.write_all(line.as_bytes())?;
+
+    let mut line = String::new();
+    BufReader::new(stream).read_line(&mut line)?;
+    let response: SignerResponse = serde_json::from_str(&line)?;
+
+    match response.error {
+        Some(e) => Err(anyhow!("Remote signer: {}", e)),
+        None => Ok(response),
+    }
+}
+
+/// Reference signing service that holds a keypair and serves [RemoteSigner] requests. It only
+/// signs messages relaying data through the Moebius program, paid for by the authority or by
+/// one of the expected fee payers.
+pub struct SignerServer {
+    keypair: Keypair,
+    fee_payers: Vec<Pubkey>,
+}
+
+impl SignerServer {
+    pub fn new(keypair: Keypair) -> SignerServer {
+        let fee_payers = vec![keypair.pubkey()];
+        Self {
+            keypair,
+            fee_payers,
+        }
+    }
+
+    /// Also signs messages paid for by `fee_payers`.
+    pub fn with_fee_payers(mut self, fee_payers: Vec<Pubkey>) -> Self {
+        self.fee_payers.extend(fee_payers);
+        self
+    }
+
+    /// Binds the Unix socket (readable and writable only by the current user) and serves
+    /// requests until the process is killed.
+    pub fn serve<P: AsRef<Path>>(self, socket: P) -> anyhow::Result<()> {
+        let socket = socket.as_ref();
+        if socket.exists() {
+            std::fs::remove_file(socket)?;
+        }
+        // The socket is created with the umask, so it must already be restrictive when binding:
+        // changing the permissions afterwards leaves a window for other users to connect.
+        let umask = unsafe { libc::umask(0o177) };
+        let listener = UnixListener::bind(socket);
+        unsafe { libc::umask(umask) };
+        let listener = listener?;
+        info!(
+            "Signing for {} on {}",
+            self.keypair.pubkey(),
+            socket.display()
+        );
+
+        self.serve_listener(listener);
+
+        Ok(())
+    }
+
+    fn serve_listener(self, listener: UnixListener) {
+        let server = Arc::new(self);
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let server = Arc::clone(&server);
+                    std::thread::spawn(move || {
+                        if let Err(e) = server.handle(stream) {
+                            warn!("Signer connection: {}", e);
+                        }
+                    });
+                }
+                Err(e) => warn!("Signer accept: {}", e),
+            }
+        }
+    }
+
+    fn handle(&self, stream: UnixStream) -> anyhow::Result<()> {
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let mut writer = stream;
+
+        let mut line = String::new();
+        while reader.read_line(&mut line)? > 0 {
+            let response = match serde_json::from_str(&line) {
+                Ok(SignerRequest::Pubkey) => SignerResponse {
+                    pubkey: Some(self.keypair.pubkey().to_string()),
+                    ..SignerResponse::default()
+                },
+                Ok(SignerRequest::Sign { message }) => match self.sign(&message) {
+                    Ok(signature) => SignerResponse {
+                        signature: Some(signature.to_string()),
+                        ..SignerResponse::default()
+                    },
+                    Err(e) => {
+                        warn!("Refusing to sign: {}", e);
+                        SignerResponse {
+                            error: Some(e.to_string()),
+                            ..SignerResponse::default()
+                        }
+                    }
+                },
+                Err(e) => SignerResponse {
+                    error: Some(format!("invalid request: {}", e)),
+                    ..SignerResponse::default()
+                },
+            };
+
+            let mut out = serde_json::to_string(&response)?;
+            out.push('\n');
+            writer.write_all(out.as_bytes())?;
+            line.clear();
+        }
+
+        Ok(())
+    }
+
+    fn sign(&self, message: &str) -> anyhow::Result<Signature> {
+        let message = bs58::decode(message)
+            .into_vec()
+            .map_err(|e| anyhow!("invalid message: {}", e))?;
+        self.check_message(&message)?;
+        info!("Signing message of {} bytes", message.len());
+
+        Ok(self.keypair.sign_message(&message))
+    }
+
+    /// Accepts only messages made of Moebius `UpdateData` instructions, paid for by an
+    /// expected fee payer.
+    fn check_message(&self, message: &[u8]) -> anyhow::Result<()> {
+        let message: Message =
+            bincode::deserialize(message).map_err(|e| anyhow!("invalid message: {}", e))?;
+
+        match message.account_keys.first() {
+            Some(fee_payer) if self.fee_payers.contains(fee_payer) => {}
+            Some(fee_payer) => bail!("unexpected fee payer {}", fee_payer),
+            None => bail!("message without accounts"),
+        }
+        if message.instructions.is_empty() {
+            bail!("message without instructions");
+        }
+        for instruction in &message.instructions {
+            let program_id = message
+                .account_keys
+                .get(instruction.program_id_index as usize)
+                .ok_or_else(|| anyhow!("invalid program id index"))?;
+            if *program_id != moebius_program::id() {
+                bail!("instruction for program {}", program_id);
+            }
+            if !is_update_data(&instruction.data) {
+                bail!("instruction other than UpdateData");
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Whether `data` is a packed `MoebiusInstruction::UpdateData`: tag 1, then the length of the
+/// data as a little-endian u64, then the data.
+fn is_update_data(data: &[u8]) -> bool {
+    match data.split_first() {
+        Some((1, rest)) if rest.len() >= 8 => {
+            let (len, data) = rest.split_at(8);
+            u64::from_le_bytes(len.try_into().unwrap()) == data.len() as u64
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::broadcaster::instruction_update_data;
+    use solana_sdk::system_instruction;
+
+    #[test]
+    fn test_remote_signer() {
+        let socket =
+            std::env::temp_dir().join(format!("moebius-signer-{}.sock", std::process::id()));
+        let keypair = Keypair::new();
+        let pubkey = keypair.pubkey();
+        let fee_payer = Pubkey::new_from_array([2u8; 32]);
+
+        let _ = std::fs::remove_file(&socket);
+        let listener = UnixListener::bind(&socket).unwrap();
+        let server = SignerServer::new(keypair).with_fee_payers(vec![fee_payer]);
+        std::thread::spawn(move || server.serve_listener(listener));
+
+        let signer = RemoteSigner::connect(&socket).unwrap();
+        assert_eq!(signer.pubkey(), pubkey);
+
+        let instructions = instruction_update_data(
+            &Pubkey::new_from_array([3u8; 32]),
+            &pubkey,
+            &Pubkey::new_from_array([4u8; 32]),
+            &Pubkey::new_from_array([5u8; 32]),
+            vec![1u8; 64],
+        )
+        .unwrap();
+        let message = Message::new(&instructions, Some(&fee_payer)).serialize();
+        let signature = signer.try_sign_message(&message).unwrap();
+        assert!(signature.verify(pubkey.as_ref(), &message));
+
+        // the authority may pay for its own relays.
+        let message = Message::new(&instructions, Some(&pubkey)).serialize();
+        assert!(signer.try_sign_message(&message).is_ok());
+
+        // anything else is refused.
+        let message =
+            Message::new(&instructions, Some(&Pubkey::new_from_array([6u8; 32]))).serialize();
+        assert!(signer.try_sign_message(&message).is_err());
+        let transfer = system_instruction::transfer(&pubkey, &Pubkey::new_from_array([7u8; 32]), 1);
+        let message = Message::new(&[transfer], Some(&fee_payer)).serialize();
+        assert!(signer.try_sign_message(&message).is_err());
+        assert!(signer.try_sign_message(b"moebius").is_err());
+
+        std::fs::remove_file(&socket).unwrap();
+    }
+
+    #[test]
+    fn test_is_update_data() {
+        let mut data = vec![1u8];
+        data.extend_from_slice(&3u64.to_le_bytes());
+        data.extend_from_slice(&[7u8; 3]);
+        assert!(is_update_data(&data));
+        assert!(!is_update_data(&data[..data.len() - 1]));
+        data[0] = 0;
+        assert!(!is_update_data(&data));
+        assert!(!is_update_data(&[1u8; 4]));
+        assert!(!is_update_data(&[]));
+    }
+}
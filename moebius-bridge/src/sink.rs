@@ -0,0 +1,226 @@
+use crate::{broadcaster::Broadcaster, event_log::unix_millis};
+use anyhow::anyhow;
+use async_trait::async_trait;
+use rustc_hex::{FromHex, ToHex};
+use serde::{Deserialize, Serialize};
+use solana_client::rpc_response::RpcSimulateTransactionResult;
+use solana_sdk::{pubkey::Pubkey, signature::Signature};
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+/// Where the bridge relays events to: `rpc`, `stdout` or `jsonl:<path>`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SinkKind {
+    /// Send transactions to the Solana cluster.
+    Rpc,
+    /// Print the events.
+    Stdout,
+    /// Append the events to a JSONL file, which `feed` can relay later.
+    Jsonl(PathBuf),
+}
+
+impl Default for SinkKind {
+    fn default() -> Self {
+        SinkKind::Rpc
+    }
+}
+
+impl FromStr for SinkKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "rpc" => Ok(SinkKind::Rpc),
+            "stdout" => Ok(SinkKind::Stdout),
+            _ => match s.strip_prefix("jsonl:") {
+                Some(path) if !path.is_empty() => Ok(SinkKind::Jsonl(path.into())),
+                _ => Err(anyhow!("expected rpc, stdout or jsonl:<path>")),
+            },
+        }
+    }
+}
+
+/// One event recorded by a [JsonlBroadcaster](struct.JsonlBroadcaster.html).
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct RecordedUpdate {
+    #[serde(rename = "ProgramId")]
+    pub program_id: String,
+
+    #[serde(rename = "AccountId")]
+    pub account_id: String,
+
+    #[serde(rename = "PackedData")]
+    pub packed_data: String,
+
+    /// Milliseconds since the unix epoch.
+    #[serde(rename = "RecordedAt")]
+    pub recorded_at: u64,
+}
+
+impl RecordedUpdate {
+    pub fn new(program_id: [u8; 32], account_id: [u8; 32], data: &[u8]) -> RecordedUpdate {
+        Self {
+            program_id: Pubkey::new_from_array(program_id).to_string(),
+            account_id: Pubkey::new_from_array(account_id).to_string(),
+            packed_data: data.to_hex(),
+            recorded_at: unix_millis(SystemTime::now()),
+        }
+    }
+
+    /// The target program, target account and packed data, as passed to
+    /// [Broadcaster::broadcast](trait.Broadcaster.html#tymethod.broadcast).
+    pub fn decode(&self) -> anyhow::Result<([u8; 32], [u8; 32], Vec<u8>)> {
+        Ok((
+            Pubkey::from_str(&self.program_id)?.to_bytes(),
+            Pubkey::from_str(&self.account_id)?.to_bytes(),
+            self.packed_data
+                .from_hex()
+                .map_err(|e| anyhow!("Decoding packed data: {}", e))?,
+        ))
+    }
+
+    /// Reads the events recorded in a JSONL file, in order.
+    pub fn read_all<P: AsRef<Path>>(path: P) -> anyhow::Result<Vec<RecordedUpdate>> {
+        let path = path.as_ref();
+        let file = File::open(path)
+            .map_err(|e| anyhow!("Reading recorded events {}: {}", path.display(), e))?;
+
+        let mut updates = Vec::new();
+        for (i, line) in BufReader::new(file).lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let update = serde_json::from_str(&line)
+                .map_err(|e| anyhow!("{}:{}: {}", path.display(), i + 1, e))?;
+            updates.push(update);
+        }
+
+        Ok(updates)
+    }
+}
+
+/// What the sinks answer when asked to simulate a transaction: they never build one, so there
+/// is nothing that could fail.
+pub(crate) fn simulated() -> RpcSimulateTransactionResult {
+    RpcSimulateTransactionResult {
+        err: None,
+        logs: None,
+    }
+}
+
+/// Appends one JSON line per event to a file, so that the events can be relayed in another
+/// environment later on. The file is written from a blocking task.
+pub struct JsonlBroadcaster {
+    file: Arc<Mutex<File>>,
+}
+
+impl JsonlBroadcaster {
+    pub fn open<P: AsRef<Path>>(path: P) -> anyhow::Result<JsonlBroadcaster> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+
+        Ok(Self {
+            file: Arc::new(Mutex::new(file)),
+        })
+    }
+}
+
+#[async_trait]
+impl Broadcaster for JsonlBroadcaster {
+    async fn broadcast(
+        &self,
+        program_id: [u8; 32],
+        account_id: [u8; 32],
+        data: Vec<u8>,
+    ) -> anyhow::Result<Option<Signature>> {
+        let mut line = serde_json::to_vec(&RecordedUpdate::new(program_id, account_id, &data))?;
+        line.push(b'\n');
+        let file = Arc::clone(&self.file);
+        tokio::task::spawn_blocking(move || file.lock().unwrap().write_all(&line)).await??;
+
+        Ok(None)
+    }
+
+    async fn simulate(
+        &self,
+        _program_id: [u8; 32],
+        _account_id: [u8; 32],
+        _data: Vec<u8>,
+    ) -> anyhow::Result<RpcSimulateTransactionResult> {
+        Ok(simulated())
+    }
+}
+
+/// Prints every event, e.g. to inspect the traffic of a contract.
+pub struct StdoutBroadcaster;
+
+#[async_trait]
+impl Broadcaster for StdoutBroadcaster {
+    async fn broadcast(
+        &self,
+        program_id: [u8; 32],
+        account_id: [u8; 32],
+        data: Vec<u8>,
+    ) -> anyhow::Result<Option<Signature>> {
+        println!(
+            "{}/{}: {}",
+            Pubkey::new_from_array(program_id),
+            Pubkey::new_from_array(account_id),
+            data.to_hex::<String>()
+        );
+
+        Ok(None)
+    }
+
+    async fn simulate(
+        &self,
+        _program_id: [u8; 32],
+        _account_id: [u8; 32],
+        _data: Vec<u8>,
+    ) -> anyhow::Result<RpcSimulateTransactionResult> {
+        Ok(simulated())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sink_kind() {
+        assert_eq!(SinkKind::from_str("rpc").unwrap(), SinkKind::Rpc);
+        assert_eq!(SinkKind::from_str("stdout").unwrap(), SinkKind::Stdout);
+        assert_eq!(
+            SinkKind::from_str("jsonl:events.jsonl").unwrap(),
+            SinkKind::Jsonl(PathBuf::from("events.jsonl"))
+        );
+        assert!(SinkKind::from_str("jsonl:").is_err());
+        assert!(SinkKind::from_str("kafka").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_jsonl_round_trip() {
+        let path = std::env::temp_dir().join(format!("moebius-sink-{}.jsonl", std::process::id()));
+        let sink = JsonlBroadcaster::open(&path).unwrap();
+        sink.broadcast([1u8; 32], [2u8; 32], vec![3u8; 96])
+            .await
+            .unwrap();
+        sink.broadcast([4u8; 32], [5u8; 32], vec![]).await.unwrap();
+
+        let updates = RecordedUpdate::read_all(&path).unwrap();
+        assert_eq!(updates.len(), 2);
+        assert_eq!(
+            updates[0].decode().unwrap(),
+            ([1u8; 32], [2u8; 32], vec![3u8; 96])
+        );
+        assert_eq!(updates[1].decode().unwrap(), ([4u8; 32], [5u8; 32], vec![]));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,195 @@
+use crate::filter::deserialize_pubkey;
+use serde::Deserialize;
+use solana_sdk::pubkey::Pubkey;
+use std::{
+    collections::{HashMap, HashSet},
+    time::{Duration, Instant},
+};
+
+/// The Solana program and account a `MoebiusData` event updates.
+pub type Target = ([u8; 32], [u8; 32]);
+
+/// Minimum interval between two relays to the same target, overriding the default for the
+/// matching targets. Fields that are not set match anything.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+pub struct TargetRateLimit {
+    #[serde(rename = "ProgramId", default, deserialize_with = "deserialize_pubkey")]
+    pub program_id: Option<Pubkey>,
+
+    #[serde(rename = "AccountId", default, deserialize_with = "deserialize_pubkey")]
+    pub account_id: Option<Pubkey>,
+
+    #[serde(rename = "MinIntervalMs")]
+    pub min_interval_ms: u64,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+pub struct RateLimits {
+    /// Minimum interval between two relays to the same target, `0` disables rate limiting.
+    #[serde(rename = "MinIntervalMs", default)]
+    pub min_interval_ms: u64,
+
+    /// Per-target overrides, the first matching one wins.
+    #[serde(rename = "Targets", default)]
+    pub targets: Vec<TargetRateLimit>,
+}
+
+impl RateLimits {
+    pub fn min_interval(&self, target: &Target) -> Duration {
+        let program_id = Pubkey::new_from_array(target.0);
+        let account_id = Pubkey::new_from_array(target.1);
+        let ms = self
+            .targets
+            .iter()
+            .find(|limit| {
+                limit.program_id.map_or(true, |id| id == program_id)
+                    && limit.account_id.map_or(true, |id| id == account_id)
+            })
+            .map_or(self.min_interval_ms, |limit| limit.min_interval_ms);
+
+        Duration::from_millis(ms)
+    }
+}
+
+/// Rate limits relays per target. An event that arrives before its target's minimum interval
+/// has elapsed, or while a relay to the same target is still in flight, is held back. It is
+/// replaced by any newer event for the same target, so that only the latest payload is relayed
+/// once the target is free again.
+pub struct Throttle<T> {
+    limits: RateLimits,
+    last_relayed: HashMap<Target, Instant>,
+    in_flight: HashSet<Target>,
+    pending: HashMap<Target, T>,
+}
+
+impl<T> Throttle<T> {
+    pub fn new(limits: RateLimits) -> Throttle<T> {
+        Self {
+            limits,
+            last_relayed: HashMap::new(),
+            in_flight: HashSet::new(),
+            pending: HashMap::new(),
+        }
+    }
+
+    fn due_at(&self, target: &Target) -> Option<Instant> {
+        self.last_relayed
+            .get(target)
+            .map(|last| *last + self.limits.min_interval(target))
+    }
+
+    fn is_due(&self, target: &Target, now: Instant) -> bool {
+        !self.in_flight.contains(target) && self.due_at(target).map_or(true, |due_at| due_at <= now)
+    }
+
+    /// Returns the event if it can be relayed right away. Otherwise it is kept as the pending
+    /// event of its target, and the event it superseded (if any) is returned as `Err`.
+    pub fn submit(&mut self, target: Target, event: T, now: Instant) -> Result<Option<T>, T> {
+        if self.is_due(&target, now) && !self.pending.contains_key(&target) {
+            return Ok(Some(event));
+        }
+
+        match self.pending.insert(target, event) {
+            Some(superseded) => Err(superseded),
+            None => Ok(None),
+        }
+    }
+
+    /// Records that a relay to the target has started.
+    pub fn started(&mut self, target: Target, now: Instant) {
+        self.last_relayed.insert(target, now);
+        self.in_flight.insert(target);
+    }
+
+    /// Records that the relay to the target has finished, successfully or not.
+    pub fn finished(&mut self, target: &Target) {
+        self.in_flight.remove(target);
+    }
+
+    /// Takes the pending events whose targets' intervals have elapsed.
+    pub fn due(&mut self, now: Instant) -> Vec<(Target, T)> {
+        let due_targets: Vec<Target> = self
+            .pending
+            .keys()
+            .filter(|target| self.is_due(target, now))
+            .cloned()
+            .collect();
+
+        due_targets
+            .into_iter()
+            .filter_map(|target| self.pending.remove(&target).map(|event| (target, event)))
+            .collect()
+    }
+
+    /// How long until the next pending event is due, if there is any. Events waiting for an
+    /// in-flight relay are not counted, they become due when the relay finishes.
+    pub fn next_due(&self, now: Instant) -> Option<Duration> {
+        self.pending
+            .keys()
+            .filter(|target| !self.in_flight.contains(target))
+            .map(|target| {
+                self.due_at(target)
+                    .map_or(Duration::from_secs(0), |due_at| {
+                        due_at.saturating_duration_since(now)
+                    })
+            })
+            .min()
+    }
+
+    /// Puts back an event whose relay failed, unless a newer one is already pending.
+    pub fn requeue(&mut self, target: Target, event: T) {
+        self.pending.entry(target).or_insert(event);
+    }
+
+    /// Forgets the pending and in-flight events, once they are left to another replica.
+    pub fn clear(&mut self) {
+        self.in_flight.clear();
+        self.pending.clear();
+    }
+
+    /// All pending events, regardless of whether they are due.
+    pub fn pending(&self) -> impl Iterator<Item = (&Target, &T)> {
+        self.pending.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_coalesce_pending_events() {
+        let target = ([1u8; 32], [2u8; 32]);
+        let other = ([1u8; 32], [3u8; 32]);
+        let mut throttle = Throttle::new(RateLimits {
+            min_interval_ms: 1000,
+            targets: vec![TargetRateLimit {
+                account_id: Some(Pubkey::new_from_array(other.1)),
+                min_interval_ms: 0,
+                ..TargetRateLimit::default()
+            }],
+        });
+        let now = Instant::now();
+
+        assert_eq!(throttle.submit(target, 1, now), Ok(Some(1)));
+        throttle.started(target, now);
+        throttle.finished(&target);
+
+        // held back, then superseded.
+        assert_eq!(throttle.submit(target, 2, now), Ok(None));
+        assert_eq!(throttle.submit(target, 3, now), Err(2));
+        assert_eq!(throttle.next_due(now), Some(Duration::from_millis(1000)));
+        assert!(throttle.due(now).is_empty());
+
+        // other targets are not rate limited, but still wait for in-flight relays.
+        throttle.started(other, now);
+        assert_eq!(throttle.submit(other, 4, now), Ok(None));
+        assert_eq!(throttle.next_due(now), Some(Duration::from_millis(1000)));
+        throttle.finished(&other);
+        assert_eq!(throttle.due(now), vec![(other, 4)]);
+
+        let later = now + Duration::from_millis(1000);
+        assert_eq!(throttle.due(later), vec![(target, 3)]);
+        assert_eq!(throttle.next_due(later), None);
+    }
+}
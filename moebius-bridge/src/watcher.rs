@@ -1,44 +1,697 @@
-use crate::{bindings::Moebius, broadcaster::Broadcaster};
+use crate::{
+    bindings::{Moebius, MoebiusDataFilter},
+    broadcaster::Broadcaster,
+    checkpoint::{Checkpoint, PendingRelay},
+    event_log::{unix_millis, Decision, EventLog, EventRecord, EventSource},
+    filter::RelayFilter,
+    throttle::{RateLimits, Target, Throttle},
+};
 
-use ethers::prelude::*;
-use log::info;
-use std::sync::Arc;
+use anyhow::anyhow;
+use ethers::{
+    core::abi::{self, Detokenize, ParamType},
+    prelude::*,
+};
+use futures::{
+    future::{self, AbortHandle},
+    stream::FuturesUnordered,
+};
+use log::{debug, info, warn};
+use rustc_hex::ToHex;
+use solana_sdk::{pubkey::Pubkey, signature::Signature};
+use std::{
+    collections::HashMap,
+    convert::TryFrom,
+    future::Future,
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant, SystemTime},
+};
+use tokio::task::{JoinError, JoinHandle};
 
-pub struct MoebiusWatcher<M> {
-    #[allow(dead_code)]
+/// Maximum number of blocks covered by a single `eth_getLogs` request during a replay.
+const REPLAY_BLOCK_RANGE: u64 = 1000;
+
+/// How long the watcher waits for a new log when no throttled event is pending.
+const IDLE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Selector of `Moebius.execute(address,bytes)`.
+const EXECUTE_SELECTOR: [u8; 4] = [28, 255, 121, 205];
+
+/// How long the watcher waits for the in-flight relays when stopping, by default.
+const DEFAULT_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How often the checkpoint is saved while running.
+const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(10);
+
+type RelayHandle = JoinHandle<(Target, anyhow::Result<Option<Signature>>)>;
+
+/// A decoded `MoebiusData` event on its way to Solana.
+#[derive(Clone)]
+struct Event {
+    data: MoebiusDataFilter,
+    source: EventSource,
+    received_at: SystemTime,
+    started_at: Option<SystemTime>,
+}
+
+impl Event {
+    fn new(data: MoebiusDataFilter, source: EventSource) -> Event {
+        Self {
+            data,
+            source,
+            received_at: SystemTime::now(),
+            started_at: None,
+        }
+    }
+}
+
+pub struct MoebiusWatcher<M, B> {
     client: Arc<M>,
     moebius: Moebius<M>,
-    broadcaster: Broadcaster,
+    broadcaster: Arc<B>,
+    relay_filter: RelayFilter,
+    throttle: Throttle<Event>,
+    in_flight: HashMap<Target, Event>,
+    aborts: HashMap<Target, AbortHandle>,
+    dry_run: bool,
+    checkpoint_path: Option<PathBuf>,
+    shutdown_timeout: Duration,
+    last_block: Option<u64>,
+    caught_up_to: Option<u64>,
+    event_log: Option<EventLog>,
 }
 
-impl<M: Middleware + 'static> MoebiusWatcher<M> {
+impl<M: Middleware + 'static, B: Broadcaster> MoebiusWatcher<M, B> {
     pub fn new(
         client: Arc<M>,
         moebius_addr: Address,
-        broadcaster: Broadcaster,
-    ) -> anyhow::Result<MoebiusWatcher<M>> {
+        broadcaster: B,
+    ) -> anyhow::Result<MoebiusWatcher<M, B>> {
         let moebius = Moebius::new(moebius_addr, Arc::clone(&client));
 
         Ok(Self {
             client,
             moebius,
-            broadcaster,
+            broadcaster: Arc::new(broadcaster),
+            relay_filter: RelayFilter::default(),
+            throttle: Throttle::new(RateLimits::default()),
+            in_flight: HashMap::new(),
+            aborts: HashMap::new(),
+            dry_run: false,
+            checkpoint_path: None,
+            shutdown_timeout: DEFAULT_SHUTDOWN_TIMEOUT,
+            last_block: None,
+            caught_up_to: None,
+            event_log: None,
         })
     }
 
-    pub async fn run(&mut self) -> anyhow::Result<()> {
-        let mut stream = self.moebius.moebius_data_filter().stream().await?;
+    /// In dry-run mode every `MoebiusData` log is turned into a transaction that is simulated
+    /// and logged, but never sent.
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Only relay the events allowed by the given filter.
+    pub fn with_relay_filter(mut self, relay_filter: RelayFilter) -> Self {
+        self.relay_filter = relay_filter;
+        self
+    }
+
+    /// Rate limit the relays to each target, see [Throttle](struct.Throttle.html).
+    pub fn with_rate_limits(mut self, rate_limits: RateLimits) -> Self {
+        self.throttle = Throttle::new(rate_limits);
+        self
+    }
+
+    /// Resume from the checkpoint at the given path when starting, and save it when stopping.
+    pub fn with_checkpoint<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.checkpoint_path = Some(path.into());
+        self
+    }
+
+    /// How long to wait for the in-flight relays when stopping.
+    pub fn with_shutdown_timeout(mut self, timeout: Duration) -> Self {
+        self.shutdown_timeout = timeout;
+        self
+    }
+
+    /// Writes one JSON line per processed event to the event log.
+    pub fn with_event_log(mut self, event_log: EventLog) -> Self {
+        self.event_log = Some(event_log);
+        self
+    }
+
+    /// Watches for `MoebiusData` logs and relays them. Relays to different targets are sent
+    /// concurrently, up to the broadcaster's limit, while relays to the same target are sent
+    /// one after the other.
+    ///
+    /// Once `shutdown` completes, or a relay fails, no more logs are taken. The in-flight relays
+    /// are given the shutdown timeout to finish, and the checkpoint is saved, if there is one.
+    pub async fn run<S: Future<Output = ()>>(&mut self, shutdown: S) -> anyhow::Result<()> {
+        self.run_leading(shutdown, future::pending()).await?;
+
+        Ok(())
+    }
+
+    /// Same as [run](#method.run), for a replica that may only relay while it holds a lease.
+    /// Once `lost` completes, whatever the watcher is doing, the in-flight relays are aborted
+    /// and the checkpoint is left as it is for the replica taking over. Returns whether the
+    /// lease was lost, in which case the watcher can run again from the checkpoint once the
+    /// lease is reacquired.
+    pub async fn run_leading<S, L>(&mut self, shutdown: S, lost: L) -> anyhow::Result<bool>
+    where
+        S: Future<Output = ()>,
+        L: Future<Output = ()>,
+    {
+        let mut relays: FuturesUnordered<RelayHandle> = FuturesUnordered::new();
+        tokio::pin!(lost);
+
+        let mut result = Ok(());
+        let mut lost_lease = tokio::select! {
+            watched = self.watch(shutdown, &mut relays) => {
+                result = watched;
+                false
+            }
+            _ = &mut lost => true,
+        };
+        if !lost_lease {
+            lost_lease = tokio::select! {
+                stopped = self.stop(relays) => {
+                    stopped?;
+                    false
+                }
+                _ = &mut lost => true,
+            };
+        }
+        if lost_lease {
+            self.abort_relays();
+            self.throttle.clear();
+            self.last_block = None;
+            self.caught_up_to = None;
+            return Ok(true);
+        }
+
+        result.map(|_| false)
+    }
+
+    /// Resumes from the checkpoint, then relays the logs as they come in until `shutdown`
+    /// completes, the stream of logs ends or a relay fails.
+    async fn watch<S: Future<Output = ()>>(
+        &mut self,
+        shutdown: S,
+        relays: &mut FuturesUnordered<RelayHandle>,
+    ) -> anyhow::Result<()> {
+        let client = Arc::clone(&self.client);
+        let filter = self.log_filter();
+        let mut stream = client.watch(&filter).await?;
+        tokio::pin!(shutdown);
+
+        self.resume(relays).await?;
+        let mut saved_at = Instant::now();
+        loop {
+            let wait = self
+                .throttle
+                .next_due(Instant::now())
+                .unwrap_or(IDLE_INTERVAL);
+
+            tokio::select! {
+                _ = &mut shutdown => {
+                    info!("Shutting down");
+                    return Ok(());
+                }
+                log = stream.next() => match log {
+                    Some(log) => self.process(&log, relays).await?,
+                    None => return Ok(()),
+                },
+                Some(relayed) = relays.next(), if !relays.is_empty() => self.finish(relayed)?,
+                _ = tokio::time::delay_for(wait) => {}
+            }
+
+            // Relay the throttled events whose target is free again.
+            for (target, event) in self.throttle.due(Instant::now()) {
+                relays.push(self.spawn_relay(target, event));
+            }
+
+            // Keep the checkpoint fresh, for a replica that takes over after a crash.
+            if saved_at.elapsed() >= CHECKPOINT_INTERVAL {
+                if let Err(e) = self.save_checkpoint() {
+                    warn!("Saving checkpoint: {}", e);
+                }
+                saved_at = Instant::now();
+            }
+        }
+    }
+
+    /// Re-submits the pending events of the checkpoint, and handles the logs emitted since its
+    /// block.
+    async fn resume(&mut self, relays: &mut FuturesUnordered<RelayHandle>) -> anyhow::Result<()> {
+        let checkpoint = match self.checkpoint_path {
+            Some(ref path) => Checkpoint::load(path)?,
+            None => return Ok(()),
+        };
+
+        for pending in checkpoint.pending.iter() {
+            let data = MoebiusDataFilter::try_from(pending)?;
+            self.submit(Event::new(data, pending.source.clone()), relays);
+        }
 
-        while let Some(item) = stream.next().await {
-            if let Ok(log) = item {
-                let tx_sig = self
-                    .broadcaster
-                    .broadcast(log.program_id, log.account_id, log.packed_data)
-                    .await?;
-                info!("UpdateData: {}", tx_sig);
+        let head = self.client.get_block_number().await?.as_u64();
+        // Without a checkpointed block, only the head block is fetched: the logs it holds may
+        // not come in through the watch, which is dropped once they are caught up on.
+        let from_block = checkpoint.block.map_or(head, |block| block + 1);
+        if from_block < head {
+            info!("Catching up from block {} to {}", from_block, head);
+        }
+        for (start, end) in block_ranges(from_block, head) {
+            for log in self.logs(start, end).await? {
+                self.process(&log, relays).await?;
             }
         }
+        self.last_block = Some(head.max(checkpoint.block.unwrap_or_default()));
+        self.caught_up_to = self.last_block;
 
         Ok(())
     }
+
+    /// Relays the decoded log if it is allowed and its target is free, otherwise keeps it as
+    /// its target's pending event.
+    async fn process(
+        &mut self,
+        log: &Log,
+        relays: &mut FuturesUnordered<RelayHandle>,
+    ) -> anyhow::Result<()> {
+        let block_number = log.block_number.map(|n| n.as_u64());
+        // Logs the catch-up already handled can still come in through the watch.
+        if block_number.is_some() && block_number <= self.caught_up_to {
+            return Ok(());
+        }
+        // The blocks before this log's are done, its own block may still have logs to come.
+        if let Some(n) = block_number {
+            self.last_block = self.last_block.max(Some(n.saturating_sub(1)));
+        }
+
+        let data = match decode_log(log) {
+            Ok(data) => data,
+            Err(e) => {
+                warn!("Decoding MoebiusData: {}", e);
+                self.dead_letter(log, e);
+                return Ok(());
+            }
+        };
+        let event = Event::new(data, EventSource::from(log));
+        if self.allowed(log, &event.data).await? {
+            self.submit(event, relays);
+        } else {
+            self.record(&event, Decision::Filtered, None);
+        }
+
+        Ok(())
+    }
+
+    fn submit(&mut self, event: Event, relays: &mut FuturesUnordered<RelayHandle>) {
+        let target = (event.data.program_id, event.data.account_id);
+        match self.throttle.submit(target, event, Instant::now()) {
+            Ok(Some(event)) => relays.push(self.spawn_relay(target, event)),
+            Ok(None) => debug!("Throttled MoebiusData for {}", display_target(&target)),
+            Err(superseded) => {
+                debug!("Coalesced MoebiusData for {}", display_target(&target));
+                self.record(&superseded, Decision::Coalesced, None);
+            }
+        }
+    }
+
+    fn spawn_relay(&mut self, target: Target, mut event: Event) -> RelayHandle {
+        self.throttle.started(target, Instant::now());
+        event.started_at = Some(SystemTime::now());
+        let data = event.data.clone();
+        self.in_flight.insert(target, event);
+
+        let broadcaster = Arc::clone(&self.broadcaster);
+        let dry_run = self.dry_run;
+        let (relayed, abort) =
+            future::abortable(async move { relay(broadcaster.as_ref(), dry_run, data).await });
+        self.aborts.insert(target, abort);
+        tokio::spawn(async move {
+            let result = relayed
+                .await
+                .unwrap_or_else(|_| Err(anyhow!("Relay aborted")));
+            (target, result)
+        })
+    }
+
+    /// Aborts the in-flight relays, recording them as failed.
+    fn abort_relays(&mut self) {
+        if !self.in_flight.is_empty() {
+            warn!("Aborting {} in-flight relays", self.in_flight.len());
+        }
+        for (_, abort) in self.aborts.drain() {
+            abort.abort();
+        }
+        for (_, event) in std::mem::take(&mut self.in_flight) {
+            self.record_relay(&event, &Err(anyhow!("Relay aborted")));
+        }
+    }
+
+    /// Frees the target of a finished relay. A failed event is put back as pending, so that it
+    /// ends up in the checkpoint. In dry-run mode, failed simulations are only recorded.
+    fn finish(
+        &mut self,
+        relayed: Result<(Target, anyhow::Result<Option<Signature>>), JoinError>,
+    ) -> anyhow::Result<()> {
+        let (target, result) = relayed?;
+        self.throttle.finished(&target);
+        self.aborts.remove(&target);
+        let event = match self.in_flight.remove(&target) {
+            Some(event) => event,
+            None => return result.map(|_| ()),
+        };
+        self.record_relay(&event, &result);
+
+        if let Err(e) = result {
+            if self.dry_run {
+                return Ok(());
+            }
+            self.throttle.requeue(target, event);
+            return Err(e);
+        }
+
+        Ok(())
+    }
+
+    fn record_relay(&mut self, event: &Event, result: &anyhow::Result<Option<Signature>>) {
+        let decision = match result {
+            Ok(_) => Decision::Relayed,
+            Err(_) => Decision::Failed,
+        };
+        self.record(event, decision, Some(result));
+    }
+
+    /// Writes the event's line to the event log, if there is one. `result` is the outcome of
+    /// the relay, for relayed or failed events.
+    fn record(
+        &mut self,
+        event: &Event,
+        decision: Decision,
+        result: Option<&anyhow::Result<Option<Signature>>>,
+    ) {
+        let (signature, error) = match result {
+            Some(Ok(signature)) => (signature.as_ref().map(|s| s.to_string()), None),
+            Some(Err(e)) => (None, Some(e.to_string())),
+            None => (None, None),
+        };
+        let record = EventRecord {
+            source: event.source.clone(),
+            program_id: Some(Pubkey::new_from_array(event.data.program_id).to_string()),
+            account_id: Some(Pubkey::new_from_array(event.data.account_id).to_string()),
+            payload: event.data.packed_data.to_hex(),
+            decision,
+            signature,
+            error,
+            dry_run: self.dry_run,
+            received_at: unix_millis(event.received_at),
+            queued_ms: event
+                .started_at
+                .map(|started_at| millis_between(event.received_at, started_at)),
+            relay_ms: event
+                .started_at
+                .filter(|_| result.is_some())
+                .map(|started_at| millis_between(started_at, SystemTime::now())),
+        };
+        self.write_record(&record);
+    }
+
+    /// Records a log that could not be decoded, along with its raw data.
+    fn dead_letter(&mut self, log: &Log, error: anyhow::Error) {
+        let record = EventRecord {
+            source: EventSource::from(log),
+            program_id: None,
+            account_id: None,
+            payload: log.data.0.to_hex(),
+            decision: Decision::DeadLettered,
+            signature: None,
+            error: Some(error.to_string()),
+            dry_run: self.dry_run,
+            received_at: unix_millis(SystemTime::now()),
+            queued_ms: None,
+            relay_ms: None,
+        };
+        self.write_record(&record);
+    }
+
+    fn write_record(&mut self, record: &EventRecord) {
+        if let Some(event_log) = self.event_log.as_mut() {
+            if let Err(e) = event_log.write(record) {
+                warn!("Writing event log: {}", e);
+            }
+        }
+    }
+
+    /// Waits for the in-flight relays, up to the shutdown timeout, and saves the checkpoint.
+    async fn stop(&mut self, mut relays: FuturesUnordered<RelayHandle>) -> anyhow::Result<()> {
+        if !relays.is_empty() {
+            info!("Waiting for {} in-flight relays", relays.len());
+        }
+
+        let timeout = self.shutdown_timeout;
+        let finish_all = async {
+            while let Some(relayed) = relays.next().await {
+                if let Err(e) = self.finish(relayed) {
+                    warn!("Relay failed during shutdown: {}", e);
+                }
+            }
+        };
+        if tokio::time::timeout(timeout, finish_all).await.is_err() {
+            warn!(
+                "Timed out waiting for in-flight relays, keeping {} of them as pending",
+                self.in_flight.len()
+            );
+        }
+
+        self.save_checkpoint()
+    }
+
+    /// Saves the last handled block, along with the pending events and the relays that did not
+    /// finish. Relaying one of the latter again on restart only rewrites the same state.
+    fn save_checkpoint(&self) -> anyhow::Result<()> {
+        let path = match self.checkpoint_path {
+            Some(ref path) => path.clone(),
+            None => return Ok(()),
+        };
+
+        // A pending event is always newer than the in-flight one for the same target.
+        let mut pending: HashMap<&Target, &Event> = self.in_flight.iter().collect();
+        pending.extend(self.throttle.pending());
+
+        let checkpoint = Checkpoint {
+            block: self.last_block,
+            pending: pending
+                .values()
+                .map(|event| PendingRelay::new(&event.data, event.source.clone()))
+                .collect(),
+        };
+        checkpoint.save(&path)?;
+        debug!(
+            "Saved checkpoint at block {:?} with {} pending events",
+            checkpoint.block,
+            checkpoint.pending.len()
+        );
+
+        Ok(())
+    }
+
+    /// Fetches the `MoebiusData` logs emitted between `from_block` and `to_block` (both
+    /// inclusive) and relays them in order. Logs for other programs or accounts than the given
+    /// ones, if any, are skipped.
+    pub async fn replay(
+        &mut self,
+        from_block: u64,
+        to_block: u64,
+        program_id: Option<Pubkey>,
+        account_id: Option<Pubkey>,
+    ) -> anyhow::Result<()> {
+        for (start, end) in block_ranges(from_block, to_block) {
+            info!("Replaying blocks {} to {}", start, end);
+
+            for log in self.logs(start, end).await? {
+                let block_number = log.block_number.unwrap_or_default();
+                let data = match decode_log(&log) {
+                    Ok(data) => data,
+                    Err(e) => {
+                        warn!("Decoding MoebiusData at block {}: {}", block_number, e);
+                        self.dead_letter(&log, e);
+                        continue;
+                    }
+                };
+                if program_id.map_or(false, |id| id.to_bytes() != data.program_id)
+                    || account_id.map_or(false, |id| id.to_bytes() != data.account_id)
+                {
+                    continue;
+                }
+                let mut event = Event::new(data, EventSource::from(&log));
+                if !self.allowed(&log, &event.data).await? {
+                    self.record(&event, Decision::Filtered, None);
+                    continue;
+                }
+
+                event.started_at = Some(SystemTime::now());
+                let result =
+                    relay(self.broadcaster.as_ref(), self.dry_run, event.data.clone()).await;
+                self.record_relay(&event, &result);
+                if !self.dry_run {
+                    result.map_err(|e| anyhow!("Replay at block {}: {}", block_number, e))?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Fetches the `MoebiusData` logs emitted between `from_block` and `to_block` (both
+    /// inclusive), in the order they were emitted.
+    async fn logs(&self, from_block: u64, to_block: u64) -> anyhow::Result<Vec<Log>> {
+        let filter = self.log_filter().from_block(from_block).to_block(to_block);
+        let mut logs = self.client.get_logs(&filter).await?;
+        logs.sort_by_key(|log| (log.block_number, log.log_index));
+
+        Ok(logs)
+    }
+
+    fn log_filter(&self) -> Filter {
+        Filter::new()
+            .address(self.moebius.address())
+            .topic0(MoebiusDataFilter::signature())
+    }
+
+    /// Whether the relay filter allows the decoded event.
+    async fn allowed(&self, log: &Log, data: &MoebiusDataFilter) -> anyhow::Result<bool> {
+        let program_id = Pubkey::new_from_array(data.program_id);
+        let account_id = Pubkey::new_from_array(data.account_id);
+        let target = if self.relay_filter.needs_target() {
+            self.target(log).await?
+        } else {
+            None
+        };
+
+        let allowed = self
+            .relay_filter
+            .allows(&program_id, &account_id, target.as_ref());
+        if !allowed {
+            info!(
+                "Filtered MoebiusData for {}/{} (target: {:?})",
+                program_id, account_id, target
+            );
+        }
+
+        Ok(allowed)
+    }
+
+    /// Finds the `_target` contract of the `Moebius.execute` call that emitted the log. This is
+    /// only possible if the transaction called `execute` directly.
+    async fn target(&self, log: &Log) -> anyhow::Result<Option<Address>> {
+        let tx_hash = match log.transaction_hash {
+            Some(tx_hash) => tx_hash,
+            None => return Ok(None),
+        };
+        let tx = self.client.get_transaction(tx_hash).await?;
+
+        Ok(tx.and_then(|tx| decode_execute_target(self.moebius.address(), &tx)))
+    }
+}
+
+/// Sends the `UpdateData` transaction, and returns its signature. In dry-run mode the
+/// transaction is only simulated, and a failed simulation is returned as an error.
+async fn relay<B: Broadcaster>(
+    broadcaster: &B,
+    dry_run: bool,
+    log: MoebiusDataFilter,
+) -> anyhow::Result<Option<Signature>> {
+    if dry_run {
+        simulate(broadcaster, log).await?;
+        return Ok(None);
+    }
+
+    let target = display_target(&(log.program_id, log.account_id));
+    let tx_sig = broadcaster
+        .broadcast(log.program_id, log.account_id, log.packed_data)
+        .await?;
+    match tx_sig {
+        Some(ref tx_sig) => info!("UpdateData: {}", tx_sig),
+        None => info!("UpdateData {}: recorded", target),
+    }
+
+    Ok(tx_sig)
+}
+
+async fn simulate<B: Broadcaster>(broadcaster: &B, log: MoebiusDataFilter) -> anyhow::Result<()> {
+    let target = display_target(&(log.program_id, log.account_id));
+
+    let result = broadcaster
+        .simulate(log.program_id, log.account_id, log.packed_data)
+        .await
+        .map_err(|e| {
+            warn!("UpdateData (dry run) {}: {}", target, e);
+            e
+        })?;
+    match result.err {
+        None => info!("UpdateData (dry run) {}: ok", target),
+        Some(ref e) => warn!("UpdateData (dry run) {}: {}", target, e),
+    }
+    for line in result.logs.unwrap_or_default() {
+        debug!("  {}", line);
+    }
+
+    match result.err {
+        None => Ok(()),
+        Some(e) => Err(anyhow!("Simulation failed: {}", e)),
+    }
+}
+
+/// Splits the inclusive block range into chunks of at most `REPLAY_BLOCK_RANGE` blocks.
+fn block_ranges(from_block: u64, to_block: u64) -> impl Iterator<Item = (u64, u64)> {
+    (from_block..=to_block)
+        .step_by(REPLAY_BLOCK_RANGE as usize)
+        .map(move |start| (start, to_block.min(start + REPLAY_BLOCK_RANGE - 1)))
+}
+
+fn millis_between(from: SystemTime, to: SystemTime) -> u64 {
+    to.duration_since(from).map_or(0, |d| d.as_millis() as u64)
+}
+
+fn display_target(target: &Target) -> String {
+    format!(
+        "{}/{}",
+        Pubkey::new_from_array(target.0),
+        Pubkey::new_from_array(target.1)
+    )
+}
+
+/// Decodes the non-indexed `MoebiusData(bytes32,bytes32,bytes)` fields of a raw log.
+fn decode_log(log: &Log) -> anyhow::Result<MoebiusDataFilter> {
+    let tokens = abi::decode(
+        &[
+            ParamType::FixedBytes(32),
+            ParamType::FixedBytes(32),
+            ParamType::Bytes,
+        ],
+        &log.data.0,
+    )?;
+
+    Ok(MoebiusDataFilter::from_tokens(tokens)?)
+}
+
+/// Decodes the `_target` argument if the transaction is a call to `Moebius.execute`.
+fn decode_execute_target(moebius: Address, tx: &Transaction) -> Option<Address> {
+    let input = &tx.input.0;
+    if tx.to != Some(moebius) || input.len() < 4 || input[..4] != EXECUTE_SELECTOR {
+        return None;
+    }
+
+    abi::decode(&[ParamType::Address, ParamType::Bytes], &input[4..])
+        .ok()?
+        .into_iter()
+        .next()?
+        .into_address()
 }
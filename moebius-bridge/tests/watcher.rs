@@ -0,0 +1,307 @@
+mod watcher_tests {
+    use async_trait::async_trait;
+    use ethers::{
+        core::{
+            abi::{self, Token},
+            utils::keccak256,
+        },
+        prelude::*,
+    };
+    use moebius::{Checkpoint, MemoryBroadcaster, MoebiusWatcher};
+    use moebius_program::instruction::MoebiusInstruction;
+    use rustc_hex::ToHex;
+    use serde::{Deserialize, Serialize};
+    use serde_json::{json, Value};
+    use solana_sdk::{
+        instruction::{AccountMeta, Instruction},
+        pubkey::Pubkey,
+    };
+    use std::{
+        collections::{HashMap, VecDeque},
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    /// Answers JSON-RPC requests with scripted responses, in order. The last response for a
+    /// method keeps being returned once the others are used up.
+    #[derive(Debug, Default)]
+    struct ScriptedClient {
+        responses: Mutex<HashMap<String, VecDeque<Value>>>,
+    }
+
+    impl ScriptedClient {
+        fn respond(self, method: &str, responses: Vec<Value>) -> Self {
+            self.responses
+                .lock()
+                .unwrap()
+                .insert(method.to_string(), responses.into_iter().collect());
+            self
+        }
+    }
+
+    #[derive(Debug, thiserror::Error)]
+    enum ScriptedError {
+        #[error("no response scripted for {0}")]
+        NotScripted(String),
+        #[error(transparent)]
+        Json(#[from] serde_json::Error),
+    }
+
+    impl From<ScriptedError> for ProviderError {
+        fn from(e: ScriptedError) -> Self {
+            ProviderError::JsonRpcClientError(Box::new(e))
+        }
+    }
+
+    #[async_trait]
+    impl JsonRpcClient for ScriptedClient {
+        type Error = ScriptedError;
+
+        async fn request<T: Serialize + Send + Sync, R: for<'a> Deserialize<'a>>(
+            &self,
+            method: &str,
+            _params: T,
+        ) -> Result<R, ScriptedError> {
+            let mut responses = self.responses.lock().unwrap();
+            let queue = responses
+                .get_mut(method)
+                .filter(|queue| !queue.is_empty())
+                .ok_or_else(|| ScriptedError::NotScripted(method.to_string()))?;
+            let response = if queue.len() > 1 {
+                queue.pop_front().unwrap()
+            } else {
+                queue[0].clone()
+            };
+
+            Ok(serde_json::from_value(response)?)
+        }
+    }
+
+    struct Relay {
+        program_id: Pubkey,
+        account_id: Pubkey,
+        packed_data: Vec<u8>,
+    }
+
+    impl Relay {
+        fn new(seed: u8) -> Relay {
+            Relay {
+                program_id: Pubkey::new(&[seed; 32]),
+                account_id: Pubkey::new(&[seed + 1; 32]),
+                packed_data: vec![seed; 96],
+            }
+        }
+
+        /// The `MoebiusData` log Moebius emits for this relay.
+        fn log(&self, moebius: Address, block: u64, log_index: u64) -> Value {
+            let data = abi::encode(&[
+                Token::FixedBytes(self.program_id.to_bytes().to_vec()),
+                Token::FixedBytes(self.account_id.to_bytes().to_vec()),
+                Token::Bytes(self.packed_data.clone()),
+            ]);
+
+            json!({
+                "address": moebius,
+                "topics": [H256::from(keccak256("MoebiusData(bytes32,bytes32,bytes)"))],
+                "data": format!("0x{}", data.to_hex::<String>()),
+                "blockNumber": U64::from(block),
+                "transactionHash": H256::repeat_byte(block as u8),
+                "logIndex": U256::from(log_index),
+            })
+        }
+
+        /// The instruction the relay must turn into, spelled out account by account.
+        fn instruction(&self, moebius_account: Pubkey, authority: Pubkey) -> Instruction {
+            let (caller_account, _) = Pubkey::find_program_address(
+                &[&self.program_id.to_bytes(), &self.account_id.to_bytes()],
+                &moebius_program::id(),
+            );
+
+            Instruction {
+                program_id: moebius_program::id(),
+                accounts: vec![
+                    AccountMeta::new(moebius_account, false),
+                    AccountMeta::new(authority, true),
+                    AccountMeta::new(caller_account, false),
+                    AccountMeta::new(self.program_id, false),
+                    AccountMeta::new(self.account_id, false),
+                ],
+                data: MoebiusInstruction::UpdateData {
+                    data: self.packed_data.clone(),
+                }
+                .pack(),
+            }
+        }
+    }
+
+    fn watcher(
+        client: ScriptedClient,
+        moebius: Address,
+        broadcaster: MemoryBroadcaster,
+    ) -> MoebiusWatcher<Provider<ScriptedClient>, MemoryBroadcaster> {
+        let provider = Provider::new(client).interval(Duration::from_millis(10));
+        MoebiusWatcher::new(Arc::new(provider), moebius, broadcaster).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_replay() {
+        let moebius = Address::repeat_byte(0xaa);
+        let (moebius_account, authority) = (Pubkey::new(&[7u8; 32]), Pubkey::new(&[8u8; 32]));
+        let relays = vec![Relay::new(1), Relay::new(3)];
+
+        let client = ScriptedClient::default().respond(
+            "eth_getLogs",
+            vec![json!([
+                relays[0].log(moebius, 100, 0),
+                relays[1].log(moebius, 101, 0)
+            ])],
+        );
+        let broadcaster = MemoryBroadcaster::new(moebius_account, authority);
+        let mut watcher = watcher(client, moebius, broadcaster.clone());
+        watcher.replay(100, 101, None, None).await.unwrap();
+
+        assert_eq!(
+            broadcaster.sent(),
+            relays
+                .iter()
+                .map(|relay| relay.instruction(moebius_account, authority))
+                .collect::<Vec<_>>()
+        );
+        assert!(broadcaster.simulated().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_replay_dry_run_and_skips() {
+        let moebius = Address::repeat_byte(0xaa);
+        let (moebius_account, authority) = (Pubkey::new(&[7u8; 32]), Pubkey::new(&[8u8; 32]));
+        let relays = vec![Relay::new(1), Relay::new(3)];
+        let mut undecodable = relays[0].log(moebius, 100, 1);
+        undecodable["data"] = json!("0x0102");
+
+        let client = ScriptedClient::default().respond(
+            "eth_getLogs",
+            vec![json!([
+                relays[0].log(moebius, 100, 0),
+                undecodable,
+                relays[1].log(moebius, 101, 0),
+            ])],
+        );
+        let broadcaster = MemoryBroadcaster::new(moebius_account, authority);
+        let mut watcher = watcher(client, moebius, broadcaster.clone()).with_dry_run(true);
+        // only relays to the second relay's account.
+        watcher
+            .replay(100, 101, None, Some(relays[1].account_id))
+            .await
+            .unwrap();
+
+        assert!(broadcaster.sent().is_empty());
+        assert_eq!(
+            broadcaster.simulated(),
+            vec![relays[1].instruction(moebius_account, authority)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_run() {
+        let moebius = Address::repeat_byte(0xaa);
+        let (moebius_account, authority) = (Pubkey::new(&[7u8; 32]), Pubkey::new(&[8u8; 32]));
+        let relays = vec![Relay::new(1), Relay::new(3)];
+
+        let client = ScriptedClient::default()
+            .respond("eth_newFilter", vec![json!("0x1")])
+            .respond(
+                "eth_getFilterChanges",
+                vec![
+                    json!([
+                        relays[0].log(moebius, 100, 0),
+                        relays[1].log(moebius, 100, 1)
+                    ]),
+                    json!([]),
+                ],
+            )
+            .respond("eth_uninstallFilter", vec![json!(true)]);
+        let broadcaster = MemoryBroadcaster::new(moebius_account, authority);
+        let mut watcher = watcher(client, moebius, broadcaster.clone());
+
+        let recorder = broadcaster.clone();
+        let relayed_all = async move {
+            while recorder.sent().len() < 2 {
+                tokio::time::delay_for(Duration::from_millis(10)).await;
+            }
+        };
+        tokio::time::timeout(Duration::from_secs(10), watcher.run(relayed_all))
+            .await
+            .expect("relays timed out")
+            .unwrap();
+
+        // Relays to different accounts are sent concurrently, in any order.
+        let mut sent = broadcaster.sent();
+        sent.sort_by_key(|instruction| instruction.accounts[4].pubkey);
+        assert_eq!(
+            sent,
+            relays
+                .iter()
+                .map(|relay| relay.instruction(moebius_account, authority))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_run_from_empty_checkpoint() {
+        let moebius = Address::repeat_byte(0xaa);
+        let (moebius_account, authority) = (Pubkey::new(&[7u8; 32]), Pubkey::new(&[8u8; 32]));
+        let relays = vec![Relay::new(1), Relay::new(3)];
+        let checkpoint = std::env::temp_dir().join(format!(
+            "moebius-watcher-checkpoint-{}.json",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&checkpoint);
+
+        // The head block's log is fetched on start, and comes in through the watch again.
+        let client = ScriptedClient::default()
+            .respond("eth_newFilter", vec![json!("0x1")])
+            .respond("eth_blockNumber", vec![json!(U64::from(100))])
+            .respond("eth_getLogs", vec![json!([relays[0].log(moebius, 100, 0)])])
+            .respond(
+                "eth_getFilterChanges",
+                vec![
+                    json!([
+                        relays[0].log(moebius, 100, 0),
+                        relays[1].log(moebius, 101, 0)
+                    ]),
+                    json!([]),
+                ],
+            )
+            .respond("eth_uninstallFilter", vec![json!(true)]);
+        let broadcaster = MemoryBroadcaster::new(moebius_account, authority);
+        let mut watcher =
+            watcher(client, moebius, broadcaster.clone()).with_checkpoint(checkpoint.clone());
+
+        let recorder = broadcaster.clone();
+        let relayed_all = async move {
+            while recorder.sent().len() < 2 {
+                tokio::time::delay_for(Duration::from_millis(10)).await;
+            }
+        };
+        tokio::time::timeout(Duration::from_secs(10), watcher.run(relayed_all))
+            .await
+            .expect("relays timed out")
+            .unwrap();
+
+        // Each log is relayed once.
+        let mut sent = broadcaster.sent();
+        sent.sort_by_key(|instruction| instruction.accounts[4].pubkey);
+        assert_eq!(
+            sent,
+            relays
+                .iter()
+                .map(|relay| relay.instruction(moebius_account, authority))
+                .collect::<Vec<_>>()
+        );
+        let saved = Checkpoint::load(&checkpoint).unwrap();
+        assert!(saved.pending.is_empty());
+        assert_eq!(saved.block, Some(100));
+
+        std::fs::remove_file(&checkpoint).unwrap();
+    }
+}
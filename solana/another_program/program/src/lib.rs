@@ -1,14 +1,45 @@
 //! Another program
 
-pub mod error;
-pub mod instruction;
-pub mod processor;
-pub mod state;
+use moebius_derive::moebius_program;
 
-#[cfg(not(feature = "no-entrypoint"))]
-mod entrypoint;
+moebius_program! {
+    /// The data bridged from Ethereum.
+    #[moebius(typed)]
+    pub struct AnotherState {
+        /// Identifies the account, updates for another id are rejected.
+        #[moebius(init_only, check_eq)]
+        pub id: bytes32,
+        pub token: address,
+        pub amount: uint256,
+        pub active: bool,
+        /// The number of updates, counted by the program.
+        #[moebius(local = "count_update")]
+        pub updates: uint64,
+    }
+
+    pub enum AnotherInstruction {
+        Initialize {
+            id: bytes32,
+            token: address,
+        },
+        UpdateState {
+            id: bytes32,
+            token: address,
+            amount: uint256,
+            active: bool,
+        },
+    }
+}
+
+/// Sets `updates` after each `UpdateState`.
+fn count_update(state: &state::AnotherState) -> u64 {
+    state.updates + 1
+}
 
 // Export current sdk types for downstream users building with a different sdk version
 pub use solana_program;
 
 solana_program::declare_id!("G33TSUoKH1xM7bPXTMoQhGQhfwWkWT8dGaW6dunDQoen");
+
+#[cfg(test)]
+mod tests;
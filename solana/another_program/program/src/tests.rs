@@ -0,0 +1,303 @@
+use crate::{
+    error::AnotherError, instruction::AnotherInstruction, processor::Processor, state::AnotherState,
+};
+use moebius_derive::types::{Address, U256};
+use solana_program::{
+    entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction},
+    program_error::ProgramError,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    sysvar::{self, rent::Rent},
+};
+use solana_sdk::account::{
+    create_account, create_is_signer_account_infos, Account as SolanaAccount,
+};
+use std::{env, fs, path::Path};
+
+fn do_process_instruction(
+    instruction: Instruction,
+    accounts: Vec<&mut SolanaAccount>,
+) -> ProgramResult {
+    let mut meta = instruction
+        .accounts
+        .iter()
+        .zip(accounts)
+        .map(|(account_meta, account)| (&account_meta.pubkey, account_meta.is_signer, account))
+        .collect::<Vec<_>>();
+
+    let account_infos = create_is_signer_account_infos(&mut meta);
+    Processor::process(&instruction.program_id, &account_infos, &instruction.data)
+}
+
+fn initialize(
+    program_id: &Pubkey,
+    account_id: &Pubkey,
+    moebius_program_id: &Pubkey,
+) -> Instruction {
+    AnotherInstruction::initialize(
+        program_id,
+        account_id,
+        moebius_program_id,
+        [1u8; 32],
+        [2u8; 20],
+    )
+    .unwrap()
+}
+
+fn update_state(
+    program_id: &Pubkey,
+    account_id: &Pubkey,
+    authority: &Pubkey,
+    id: [u8; 32],
+) -> Instruction {
+    AnotherInstruction::update_state(
+        program_id, account_id, authority, id, [4u8; 20], [5u8; 32], 1,
+    )
+    .unwrap()
+}
+
+fn pubkey_rand() -> Pubkey {
+    Pubkey::new(&rand::random::<[u8; 32]>())
+}
+
+#[test]
+fn test_instruction_builders() {
+    let program_id = pubkey_rand();
+    let account_id = pubkey_rand();
+    let moebius_program_id = pubkey_rand();
+    let authority = pubkey_rand();
+
+    let instruction = initialize(&program_id, &account_id, &moebius_program_id);
+    assert_eq!(instruction.program_id, program_id);
+    assert_eq!(
+        instruction.accounts,
+        vec![
+            AccountMeta::new(account_id, false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+        ]
+    );
+    assert_eq!(
+        AnotherInstruction::unpack(&instruction.data).unwrap(),
+        AnotherInstruction::Initialize {
+            moebius_program_id,
+            id: [1u8; 32],
+            token: [2u8; 20],
+        }
+    );
+
+    let instruction = update_state(&program_id, &account_id, &authority, [3u8; 32]);
+    assert_eq!(instruction.program_id, program_id);
+    assert_eq!(
+        instruction.accounts,
+        vec![
+            AccountMeta::new(authority, true),
+            AccountMeta::new(account_id, false),
+        ]
+    );
+    assert_eq!(
+        AnotherInstruction::unpack(&instruction.data).unwrap(),
+        AnotherInstruction::UpdateState {
+            id: [3u8; 32],
+            token: [4u8; 20],
+            amount: [5u8; 32],
+            active: 1,
+        }
+    );
+}
+
+#[test]
+fn test_initialize() {
+    let program_id = pubkey_rand();
+    let account_id = pubkey_rand();
+    let mut account = SolanaAccount::new(42, AnotherState::LEN, &program_id);
+    let mut rent_sysvar = create_account(&Rent::default(), 42);
+    let moebius_program_id = pubkey_rand();
+
+    // when the account is not rent exempt.
+    assert_eq!(
+        Err(AnotherError::NotRentExempt.into()),
+        do_process_instruction(
+            initialize(&program_id, &account_id, &moebius_program_id),
+            vec![&mut account, &mut rent_sysvar],
+        )
+    );
+
+    account.lamports = Rent::default().minimum_balance(AnotherState::LEN);
+    do_process_instruction(
+        initialize(&program_id, &account_id, &moebius_program_id),
+        vec![&mut account, &mut rent_sysvar],
+    )
+    .unwrap();
+
+    // initialize twice.
+    assert_eq!(
+        Err(AnotherError::AlreadyInUse.into()),
+        do_process_instruction(
+            initialize(&program_id, &account_id, &moebius_program_id),
+            vec![&mut account, &mut rent_sysvar],
+        )
+    );
+
+    let (authority, _) = Pubkey::find_program_address(
+        &[&program_id.to_bytes(), &account_id.to_bytes()],
+        &moebius_program_id,
+    );
+    let state = AnotherState::unpack(&account.data).unwrap();
+    assert!(state.is_initialized);
+    assert_eq!(state.authority, authority);
+    assert_eq!(state.id, [1u8; 32]);
+    assert_eq!(state.token, Address([2u8; 20]));
+    assert_eq!(state.amount, U256::zero());
+    assert_eq!(state.updates, 0);
+}
+
+#[test]
+fn test_update_state() {
+    let program_id = pubkey_rand();
+    let account_id = pubkey_rand();
+    let mut account = SolanaAccount::new(
+        Rent::default().minimum_balance(AnotherState::LEN),
+        AnotherState::LEN,
+        &program_id,
+    );
+    let mut rent_sysvar = create_account(&Rent::default(), 42);
+    let moebius_program_id = pubkey_rand();
+    let (authority_key, _) = Pubkey::find_program_address(
+        &[&program_id.to_bytes(), &account_id.to_bytes()],
+        &moebius_program_id,
+    );
+    let mut authority = SolanaAccount::default();
+
+    // before the account is initialized.
+    assert_eq!(
+        Err(ProgramError::UninitializedAccount),
+        do_process_instruction(
+            update_state(&program_id, &account_id, &authority_key, [1u8; 32]),
+            vec![&mut authority, &mut account],
+        )
+    );
+
+    do_process_instruction(
+        initialize(&program_id, &account_id, &moebius_program_id),
+        vec![&mut account, &mut rent_sysvar],
+    )
+    .unwrap();
+
+    assert_eq!(
+        Err(AnotherError::Unauthorized.into()),
+        do_process_instruction(
+            update_state(&program_id, &account_id, &pubkey_rand(), [1u8; 32]),
+            vec![&mut authority, &mut account],
+        )
+    );
+
+    // the update is for another id.
+    assert_eq!(
+        Err(AnotherError::InvalidAccount.into()),
+        do_process_instruction(
+            update_state(&program_id, &account_id, &authority_key, [3u8; 32]),
+            vec![&mut authority, &mut account],
+        )
+    );
+
+    do_process_instruction(
+        update_state(&program_id, &account_id, &authority_key, [1u8; 32]),
+        vec![&mut authority, &mut account],
+    )
+    .unwrap();
+
+    let state = AnotherState::unpack(&account.data).unwrap();
+    assert_eq!(state.authority, authority_key);
+    assert_eq!(state.id, [1u8; 32]);
+    assert_eq!(state.token, Address([4u8; 20]));
+    assert_eq!(state.amount, U256::from([5u8; 32]));
+    assert_eq!(state.active, 1);
+    assert_eq!(state.updates, 1);
+}
+
+#[test]
+fn test_update_local_field() {
+    let program_id = pubkey_rand();
+    let account_id = pubkey_rand();
+    let mut account = SolanaAccount::new(
+        Rent::default().minimum_balance(AnotherState::LEN),
+        AnotherState::LEN,
+        &program_id,
+    );
+    let mut rent_sysvar = create_account(&Rent::default(), 42);
+    let moebius_program_id = pubkey_rand();
+    let (authority_key, _) = Pubkey::find_program_address(
+        &[&program_id.to_bytes(), &account_id.to_bytes()],
+        &moebius_program_id,
+    );
+    let mut authority = SolanaAccount::default();
+
+    do_process_instruction(
+        initialize(&program_id, &account_id, &moebius_program_id),
+        vec![&mut account, &mut rent_sysvar],
+    )
+    .unwrap();
+
+    for updates in 1..=3 {
+        do_process_instruction(
+            update_state(&program_id, &account_id, &authority_key, [1u8; 32]),
+            vec![&mut authority, &mut account],
+        )
+        .unwrap();
+        assert_eq!(
+            AnotherState::unpack(&account.data).unwrap().updates,
+            updates
+        );
+    }
+
+    // a rejected update is not counted.
+    assert!(do_process_instruction(
+        update_state(&program_id, &account_id, &authority_key, [3u8; 32]),
+        vec![&mut authority, &mut account],
+    )
+    .is_err());
+    assert_eq!(AnotherState::unpack(&account.data).unwrap().updates, 3);
+}
+
+/// Compares the generated Solidity encoder, its ABI and the IDL with the committed ones. With
+/// `MOEBIUS_GENERATED=overwrite` the out of date files are rewritten, but the test still fails
+/// until they are committed and the tests run again.
+#[test]
+fn test_generated_files() {
+    let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let ethereum = manifest_dir.join("../../../ethereum");
+    let generated_files = [
+        (
+            ethereum.join("contracts/libraries/AnotherStateEncoder.sol"),
+            AnotherState::SOLIDITY_ENCODER,
+        ),
+        (
+            ethereum.join("abi/AnotherStateEncoder.json"),
+            AnotherState::ENCODER_ABI,
+        ),
+        (manifest_dir.join("idl.json"), crate::IDL),
+    ];
+    let overwrite = env::var("MOEBIUS_GENERATED").as_deref() == Ok("overwrite");
+    let mut out_of_date = vec![];
+    for (path, generated) in generated_files.iter() {
+        if fs::read_to_string(path).ok().as_deref() == Some(*generated) {
+            continue;
+        }
+        if overwrite {
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, generated).unwrap();
+        }
+        out_of_date.push(path.display().to_string());
+    }
+    assert!(
+        out_of_date.is_empty(),
+        "out of date: {}, {}",
+        out_of_date.join(", "),
+        if overwrite {
+            "rewrote them"
+        } else {
+            "run the tests with MOEBIUS_GENERATED=overwrite"
+        }
+    );
+}
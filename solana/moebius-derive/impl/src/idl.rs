@@ -0,0 +1,146 @@
+use serde_json::{json, Value};
+
+use crate::sol_type::{FieldAttrs, SolType};
+
+/// The rent sysvar's address, as in `solana_program::sysvar::rent::id()`.
+const RENT_SYSVAR_ID: &str = "SysvarRent111111111111111111111111111111111";
+
+/// The JSON IDL of a state generated by `moebius_state`, the `State` section of a program's
+/// IDL on its own.
+pub fn state(state_name: &str, state_fields: &[(String, SolType, FieldAttrs)]) -> String {
+    to_string(&json!({ "State": state_layout(state_name, state_fields) }))
+}
+
+/// The JSON IDL of a program generated by `moebius_program`.
+///
+/// Offsets are in bytes, from the start of the state account or from the byte following the
+/// instruction tag. `UpdateState` fields are ABI-encoded, so their offset and size are those of
+/// their head, which holds the offset of the contents for dynamic types. State fields list
+/// their `init_only`, `local` and `check_eq` attributes.
+pub fn program(
+    state_name: &str,
+    state_fields: &[(String, SolType, FieldAttrs)],
+    instruction_name: &str,
+    initialize_fields: &[(String, SolType)],
+    update_state_fields: &[(String, SolType)],
+) -> String {
+    let mut initialize_layout = vec![field("moebius_program_id", "pubkey", 0, 32, None)];
+    initialize_layout.extend(packed_layout(initialize_fields, 32));
+
+    let mut update_state_layout = vec![];
+    let mut offset = 0;
+    for (name, sol_type) in update_state_fields {
+        update_state_layout.push(sol_field(name, sol_type, offset, sol_type.abi_size()));
+        offset += sol_type.abi_size();
+    }
+
+    let idl = json!({
+        "State": state_layout(state_name, state_fields),
+        "Instruction": {
+            "Name": instruction_name,
+            "Variants": [
+                {
+                    "Name": "Initialize",
+                    "Tag": 0,
+                    "Encoding": "Packed",
+                    "Fields": initialize_layout,
+                    "Accounts": [
+                        account("state", None, false, true),
+                        account("rent", Some(RENT_SYSVAR_ID), false, false),
+                    ],
+                },
+                {
+                    "Name": "UpdateState",
+                    "Tag": 1,
+                    "Encoding": "Abi",
+                    "Fields": update_state_layout,
+                    "Accounts": [
+                        account("authority", None, true, true),
+                        account("state", None, false, true),
+                    ],
+                },
+            ],
+        },
+    });
+    to_string(&idl)
+}
+
+fn to_string(idl: &Value) -> String {
+    let mut idl = serde_json::to_string_pretty(idl).unwrap();
+    idl.push('\n');
+    idl
+}
+
+/// The state fields, after `is_initialized` and `authority`, with their attributes.
+fn state_layout(state_name: &str, state_fields: &[(String, SolType, FieldAttrs)]) -> Value {
+    let mut layout = vec![
+        field("is_initialized", "bool", 0, 1, None),
+        field("authority", "pubkey", 1, 32, None),
+    ];
+    let mut size = 33;
+    for (name, sol_type, attrs) in state_fields {
+        let mut field = sol_field(name, sol_type, size, sol_type.packed_size());
+        for (key, attr) in &[
+            ("InitOnly", &attrs.init_only),
+            ("Local", &attrs.local),
+            ("CheckEq", &attrs.check_eq),
+        ] {
+            if attr.is_some() {
+                field[key] = json!(true);
+            }
+        }
+        layout.push(field);
+        size += sol_type.packed_size();
+    }
+
+    json!({
+        "Name": state_name,
+        "Size": size,
+        "Fields": layout,
+    })
+}
+
+/// Fields packed one after the other, as in the state account, from `offset`.
+fn packed_layout(fields: &[(String, SolType)], mut offset: usize) -> Vec<Value> {
+    let mut layout = vec![];
+    for (name, sol_type) in fields {
+        layout.push(sol_field(name, sol_type, offset, sol_type.packed_size()));
+        offset += sol_type.packed_size();
+    }
+    layout
+}
+
+fn sol_field(name: &str, sol_type: &SolType, offset: usize, size: usize) -> Value {
+    let max_len = match sol_type {
+        SolType::Bytes(max_len) | SolType::String(max_len) | SolType::Array(_, max_len) => {
+            Some(*max_len)
+        }
+        _ => None,
+    };
+    field(name, &sol_type.sol_name(), offset, size, max_len)
+}
+
+fn field(name: &str, ty: &str, offset: usize, size: usize, max_len: Option<usize>) -> Value {
+    let mut field = json!({
+        "Name": name,
+        "Type": ty,
+        "Offset": offset,
+        "Size": size,
+    });
+    if let Some(max_len) = max_len {
+        field["MaxLen"] = json!(max_len);
+    }
+    field
+}
+
+fn account(name: &str, address: Option<&str>, is_signer: bool, is_writable: bool) -> Value {
+    let mut account = json!({
+        "Name": name,
+        "IsSigner": is_signer,
+        "IsWritable": is_writable,
+    });
+    if let Some(address) = address {
+        account["Address"] = json!(address);
+    }
+    account
+}
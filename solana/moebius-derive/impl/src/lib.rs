@@ -1,17 +1,35 @@
+/// Quotes generated code with `macro_rules!` hygiene, so that its local variables never clash
+/// with the fields it handles, which keep the caller's spans.
+macro_rules! quote {
+    ($($tt:tt)*) => {
+        quote::quote_spanned!(proc_macro2::Span::mixed_site()=> $($tt)*)
+    };
+}
+
+mod idl;
 mod moebius_instruction;
+mod moebius_program;
 mod moebius_state;
+mod sol_type;
+mod solidity;
 
 use proc_macro::TokenStream;
 use syn::parse_macro_input;
 
-use crate::{moebius_instruction::MoebiusInstruction, moebius_state::MoebiusState};
+use crate::{
+    moebius_instruction::MoebiusInstruction, moebius_program::MoebiusProgram,
+    moebius_state::MoebiusState,
+};
 
 #[proc_macro_attribute]
 pub fn moebius_state(attr: TokenStream, item: TokenStream) -> TokenStream {
     let _ = attr;
     let state = parse_macro_input!(item as MoebiusState);
 
-    state.expand().into()
+    state
+        .expand()
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
 }
 
 #[proc_macro_attribute]
@@ -19,5 +37,18 @@ pub fn moebius_instruction(attr: TokenStream, item: TokenStream) -> TokenStream
     let _ = attr;
     let instruction = parse_macro_input!(item as MoebiusInstruction);
 
-    instruction.expand().into()
+    instruction
+        .expand()
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+#[proc_macro]
+pub fn moebius_program(item: TokenStream) -> TokenStream {
+    let program = parse_macro_input!(item as MoebiusProgram);
+
+    program
+        .expand()
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
 }
@@ -1,138 +1,223 @@
 use proc_macro2::{Span, TokenStream as TokenStream2};
-use quote::quote;
-use syn::{parse::ParseBuffer, Error, Fields, Ident, ItemEnum, Type};
+use syn::{parse::ParseBuffer, Error, Field, Fields, Ident, ItemEnum};
+
+use crate::sol_type::{decode_abi_usize, passthrough_attrs, FieldAttrs, SolType};
+
+/// Arguments of the generated `initialize` and `update_state` builders, besides the fields.
+const RESERVED_ARGS: [&str; 3] = ["program_id", "state_account_id", "authority"];
 
 pub struct MoebiusInstruction {
     ast: ItemEnum,
 }
 
 impl MoebiusInstruction {
-    pub fn expand(&self) -> TokenStream2 {
+    pub fn expand(&self) -> Result<TokenStream2, Error> {
+        if !self.ast.generics.params.is_empty() {
+            return Err(Error::new_spanned(
+                &self.ast.generics,
+                "moebius_instruction does not support generics",
+            ));
+        }
+
         let mut initialize_fields = vec![quote! { moebius_program_id }];
         let mut initialize_fields_ty = vec![quote! { Pubkey }];
+        let mut initialize_fields_attrs =
+            vec![quote! { #[doc = "The Moebius program allowed to update the state."] }];
+        let mut initialize_attrs = quote! {};
         let mut initialize_pack_instructions =
-            vec![quote! { buf.extend_from_slice(moebius_program_id.as_ref()) }];
+            vec![quote! { buf.extend_from_slice(moebius_program_id.as_ref()); }];
         let mut initialize_unpack_instructions =
-            vec![quote! { let (moebius_program_id, rest) = Self::unpack_pubkey(rest)? }];
+            vec![quote! { let (moebius_program_id, rest) = Self::unpack_pubkey(rest)?; }];
 
         let mut update_state_fields = vec![];
         let mut update_state_fields_ty = vec![];
-        let mut update_state_pack_instructions = vec![];
-        let mut update_state_unpack_instructions = vec![];
+        let mut update_state_fields_attrs = vec![];
+        let mut update_state_attrs = quote! {};
+        let mut update_state_types = vec![];
 
         for variant in self.ast.variants.iter() {
-            match &variant.fields {
-                Fields::Named(fields_named) => {
-                    for field in fields_named.named.iter() {
-                        let field_ident = field.ident.clone().unwrap();
-
-                        let field_slice = format!("{}_slice", field_ident.to_string());
-                        let field_ident_slice = Ident::new(&field_slice, Span::call_site());
-
-                        if let Type::Path(ref p) = field.ty {
-                            let input_ty = p.path.segments[0].ident.to_string();
-                            let field_ty = match input_ty.as_ref() {
-                                "address" => quote! { [u8; 20] },
-                                "bytes32" | "uint256" => quote! { [u8; 32] },
-                                "bool" | "uint8" => quote! { u8 },
-                                _ => panic!("unexpected type"),
-                            };
-                            match variant.ident.to_string().as_ref() {
-                                "Initialize" => {
-                                    initialize_fields.push(quote! { #field_ident });
-                                    initialize_fields_ty.push(field_ty);
-                                    let (pack_inst, unpack_inst) = match input_ty.as_ref() {
-                                        "address" => (
-                                            quote! { buf.extend_from_slice(&#field_ident[..]) },
-                                            quote! {
-                                                let (#field_ident_slice, rest) = rest.split_at(20);
-                                                let mut #field_ident = [0u8; 20];
-                                                #field_ident.copy_from_slice(&#field_ident_slice[..]);
-                                            },
-                                        ),
-                                        "bytes32" | "uint256" => (
-                                            quote! { buf.extend_from_slice(&#field_ident[..]) },
-                                            quote! {
-                                                let (#field_ident_slice, rest) = rest.split_at(32);
-                                                let mut #field_ident = [0u8; 32];
-                                                #field_ident.copy_from_slice(&#field_ident_slice[..]);
-                                            },
-                                        ),
-                                        "bool" | "uint8" => (
-                                            quote! { buf.push(*#field_ident) },
-                                            quote! {
-                                                let (&#field_ident, rest) = rest.split_first().ok_or(InvalidInstruction)?;
-                                            },
-                                        ),
-                                        _ => panic!("unexpected type"),
-                                    };
-                                    initialize_pack_instructions.push(pack_inst);
-                                    initialize_unpack_instructions.push(unpack_inst);
-                                }
-                                "UpdateState" => {
-                                    update_state_fields.push(quote! { #field_ident });
-                                    update_state_fields_ty.push(field_ty);
-                                    let (pack_inst, unpack_inst) = match input_ty.as_ref() {
-                                        "address" => (
-                                            quote! {
-                                                buf.extend_from_slice(&[0u8; 12]);
-                                                buf.extend_from_slice(&#field_ident[..]);
-                                            },
-                                            quote! {
-                                                let (#field_ident_slice, rest) = rest.split_at(32);
-                                                let mut #field_ident = [0u8; 20];
-                                                #field_ident.copy_from_slice(&#field_ident_slice[12..]);
-                                            },
-                                        ),
-                                        "bytes32" | "uint256" => (
-                                            quote! { buf.extend_from_slice(&#field_ident[..]) },
-                                            quote! {
-                                                let (#field_ident_slice, rest) = rest.split_at(32);
-                                                let mut #field_ident = [0u8; 32];
-                                                #field_ident.copy_from_slice(&#field_ident_slice[..]);
-                                            },
-                                        ),
-                                        "bool" | "uint8" => (
-                                            quote! { buf.push(*#field_ident) },
-                                            quote! {
-                                                let (&#field_ident, rest) = rest.split_first().ok_or(InvalidInstruction)?;
-                                            },
-                                        ),
-                                        _ => panic!("unexpected type"),
-                                    };
-                                    update_state_pack_instructions.push(pack_inst);
-                                    update_state_unpack_instructions.push(unpack_inst);
-                                }
-                                _ => panic!("unexpected variant"),
-                            }
+            let variant_name = variant.ident.to_string();
+            if variant_name != "Initialize" && variant_name != "UpdateState" {
+                return Err(Error::new_spanned(
+                    &variant.ident,
+                    "unexpected variant, expected \"Initialize\" or \"UpdateState\"",
+                ));
+            }
+            if let Some((_, discriminant)) = &variant.discriminant {
+                return Err(Error::new_spanned(
+                    discriminant,
+                    "moebius_instruction assigns the discriminants itself",
+                ));
+            }
+            if variant_name == "Initialize" {
+                initialize_attrs = passthrough_attrs(&variant.attrs);
+            } else {
+                update_state_attrs = passthrough_attrs(&variant.attrs);
+            }
+            let fields: Vec<_> = match &variant.fields {
+                Fields::Named(fields_named) => fields_named.named.iter().collect(),
+                Fields::Unit => vec![],
+                Fields::Unnamed(fields_unnamed) => {
+                    return Err(Error::new_spanned(
+                        fields_unnamed,
+                        "expected named fields, e.g. `UpdateState { amount: uint256 }`",
+                    ))
+                }
+            };
+
+            for field in fields {
+                let field_ident = field.ident.clone().unwrap();
+                if field_ident == "moebius_program_id" {
+                    return Err(Error::new_spanned(
+                        &field_ident,
+                        "\"moebius_program_id\" is a field moebius_instruction adds itself",
+                    ));
+                }
+                if RESERVED_ARGS.iter().any(|arg| field_ident == arg) {
+                    return Err(Error::new_spanned(
+                        &field_ident,
+                        format!(
+                            "\"{}\" is an argument of the instruction builders",
+                            field_ident
+                        ),
+                    ));
+                }
+
+                if let Some(path) = FieldAttrs::parse(&field.attrs)?.state_only() {
+                    return Err(Error::new_spanned(
+                        path,
+                        "this attribute only applies to the fields of the state",
+                    ));
+                }
+
+                let field_slice = format!("{}_slice", field_ident.to_string());
+                let field_ident_slice = Ident::new(&field_slice, Span::mixed_site());
+
+                let sol_type = SolType::parse_field(field)?;
+                let field_ty = sol_type.rust_type();
+                if variant_name == "Initialize" {
+                    initialize_fields.push(quote! { #field_ident });
+                    initialize_fields_ty.push(field_ty);
+                    initialize_fields_attrs.push(passthrough_attrs(&field.attrs));
+                    // packed as in the state account
+                    let size = sol_type.packed_size();
+                    let check_len = sol_type.check_len(
+                        &quote! { #field_ident },
+                        &quote! { InvalidInstruction.into() },
+                    );
+                    let pack = sol_type.pack(&quote! { #field_ident }, &quote! { dst });
+                    let unpack = sol_type.unpack(
+                        &quote! { #field_ident_slice },
+                        &quote! { ProgramError::from(InvalidInstruction) },
+                    );
+                    initialize_pack_instructions.push(quote! {
+                        #check_len
+                        let start = buf.len();
+                        buf.resize(start + #size, 0);
+                        let dst = &mut buf[start..];
+                        #pack
+                    });
+                    initialize_unpack_instructions.push(quote! {
+                        if rest.len() < #size {
+                            return Err(InvalidInstruction.into());
                         }
-                    }
+                        let (#field_ident_slice, rest) = rest.split_at(#size);
+                        let #field_ident = #unpack;
+                    });
+                } else {
+                    update_state_fields.push(quote! { #field_ident });
+                    update_state_fields_ty.push(field_ty);
+                    update_state_fields_attrs.push(passthrough_attrs(&field.attrs));
+                    update_state_types.push((field_ident, field_ident_slice, sol_type));
                 }
-                _ => {}
             }
         }
 
+        // `UpdateState` is ABI-encoded, as relayed from Ethereum: the static fields and the
+        // offsets of the dynamic ones come first, followed by the dynamic fields.
+        let heads_size: usize = update_state_types
+            .iter()
+            .map(|(_, _, sol_type)| sol_type.abi_size())
+            .sum();
+        let has_dynamic = update_state_types
+            .iter()
+            .any(|(_, _, sol_type)| sol_type.is_dynamic());
+        let mut update_state_pack_instructions = vec![];
+        let mut update_state_unpack_instructions = vec![quote! {
+            if rest.len() < #heads_size {
+                return Err(InvalidInstruction.into());
+            }
+        }];
+        let mut head = 0;
+        for (field_ident, field_ident_slice, sol_type) in update_state_types.iter() {
+            let encode = sol_type.encode_abi(&quote! { #field_ident });
+            let decode = sol_type.decode_abi(&quote! { #field_ident_slice });
+            let head_end = head + sol_type.abi_size();
+            if sol_type.is_dynamic() {
+                let offset = decode_abi_usize(&quote! { rest[#head..#head_end] });
+                let check_len = sol_type.check_len(
+                    &quote! { #field_ident },
+                    &quote! { InvalidInstruction.into() },
+                );
+                update_state_pack_instructions.push(quote! {
+                    #check_len
+                    buf.extend_from_slice(&[0u8; 28]);
+                    buf.extend_from_slice(&((#heads_size + tail.len()) as u32).to_be_bytes());
+                    {
+                        let buf = &mut tail;
+                        #encode
+                    }
+                });
+                update_state_unpack_instructions.push(quote! {
+                    let #field_ident_slice = rest.get(#offset..).ok_or(InvalidInstruction)?;
+                    let #field_ident = #decode;
+                });
+            } else {
+                update_state_pack_instructions.push(encode);
+                update_state_unpack_instructions.push(quote! {
+                    let #field_ident_slice = &rest[#head..#head_end];
+                    let #field_ident = #decode;
+                });
+            }
+            head = head_end;
+        }
+        if has_dynamic {
+            update_state_pack_instructions.insert(0, quote! { let mut tail = Vec::new(); });
+            update_state_pack_instructions.push(quote! { buf.extend_from_slice(&tail); });
+        }
+
+        // the builders take `moebius_program_id` by reference, with the accounts
+        let initialize_args = &initialize_fields[1..];
+        let initialize_args_ty = &initialize_fields_ty[1..];
+
+        let attrs = passthrough_attrs(&self.ast.attrs);
         let vis = &self.ast.vis;
         let ident = &self.ast.ident;
 
-        quote! {
+        Ok(quote! {
+            #attrs
             #[repr(C)]
             #[derive(Clone, Debug, PartialEq)]
             #vis enum #ident {
+                #initialize_attrs
                 Initialize {
                     #(
-                        #initialize_fields: #initialize_fields_ty
-                    ),*,
+                        #initialize_fields_attrs
+                        #initialize_fields: #initialize_fields_ty,
+                    )*
                 },
+                #update_state_attrs
                 UpdateState {
                     #(
-                        #update_state_fields: #update_state_fields_ty
-                    ),*,
+                        #update_state_fields_attrs
+                        #update_state_fields: #update_state_fields_ty,
+                    )*
                 },
             }
 
             impl #ident {
-                pub fn pack(&self) -> Vec<u8> {
+                /// Packs the instruction data: the tag, then the fields.
+                pub fn pack(&self) -> Result<Vec<u8>, ProgramError> {
                     let mut buf = Vec::with_capacity(size_of::<Self>());
                     match self {
                         Self::Initialize {
@@ -143,7 +228,7 @@ impl MoebiusInstruction {
                             buf.push(0);
                             #(
                                 #initialize_pack_instructions
-                            );*;
+                            )*
                         }
                         Self::UpdateState {
                             #(
@@ -153,18 +238,19 @@ impl MoebiusInstruction {
                             buf.push(1);
                             #(
                                 #update_state_pack_instructions
-                            );*;
+                            )*
                         }
                     }
-                    buf
+                    Ok(buf)
                 }
+                /// Unpacks instruction data written by `pack`.
                 pub fn unpack(input: &[u8]) -> Result<Self, ProgramError> {
                     let (&tag, rest) = input.split_first().ok_or(InvalidInstruction)?;
                     Ok(match tag {
                         0 => {
                             #(
                                 #initialize_unpack_instructions
-                            );*;
+                            )*
                             Self::Initialize {
                                 #(
                                     #initialize_fields
@@ -174,7 +260,7 @@ impl MoebiusInstruction {
                         1 => {
                             #(
                                 #update_state_unpack_instructions
-                            );*;
+                            )*
                             Self::UpdateState {
                                 #(
                                     #update_state_fields
@@ -185,6 +271,62 @@ impl MoebiusInstruction {
                         _ => return Err(InvalidInstruction.into()),
                     })
                 }
+                /// Creates an `Initialize` instruction.
+                ///
+                /// Accounts: the `[writable]` state account and the rent sysvar.
+                #[allow(clippy::too_many_arguments)]
+                pub fn initialize(
+                    program_id: &Pubkey,
+                    state_account_id: &Pubkey,
+                    moebius_program_id: &Pubkey,
+                    #(#initialize_args: #initialize_args_ty),*
+                ) -> Result<solana_program::instruction::Instruction, ProgramError> {
+                    let data = Self::Initialize {
+                        moebius_program_id: *moebius_program_id,
+                        #(#initialize_args),*
+                    }
+                    .pack()?;
+
+                    let accounts = vec![
+                        solana_program::instruction::AccountMeta::new(*state_account_id, false),
+                        solana_program::instruction::AccountMeta::new_readonly(
+                            solana_program::sysvar::rent::id(),
+                            false,
+                        ),
+                    ];
+
+                    Ok(solana_program::instruction::Instruction {
+                        program_id: *program_id,
+                        accounts,
+                        data,
+                    })
+                }
+                /// Creates an `UpdateState` instruction.
+                ///
+                /// Accounts: the `[signer]` authority and the `[writable]` state account.
+                #[allow(clippy::too_many_arguments)]
+                pub fn update_state(
+                    program_id: &Pubkey,
+                    state_account_id: &Pubkey,
+                    authority: &Pubkey,
+                    #(#update_state_fields: #update_state_fields_ty),*
+                ) -> Result<solana_program::instruction::Instruction, ProgramError> {
+                    let data = Self::UpdateState {
+                        #(#update_state_fields),*
+                    }
+                    .pack()?;
+
+                    let accounts = vec![
+                        solana_program::instruction::AccountMeta::new(*authority, true),
+                        solana_program::instruction::AccountMeta::new(*state_account_id, false),
+                    ];
+
+                    Ok(solana_program::instruction::Instruction {
+                        program_id: *program_id,
+                        accounts,
+                        data,
+                    })
+                }
                 fn unpack_pubkey(input: &[u8]) -> Result<(Pubkey, &[u8]), ProgramError> {
                     if input.len() >= 32 {
                         let (key, rest) = input.split_at(32);
@@ -195,7 +337,24 @@ impl MoebiusInstruction {
                     }
                 }
             }
-        }
+        })
+    }
+}
+
+impl MoebiusInstruction {
+    pub fn ident(&self) -> &Ident {
+        &self.ast.ident
+    }
+
+    /// The fields declared on the `Initialize` or `UpdateState` variant, without the ones
+    /// `expand` adds.
+    pub fn variant_fields(&self, variant_name: &str) -> Vec<&Field> {
+        self.ast
+            .variants
+            .iter()
+            .filter(|variant| variant.ident == variant_name)
+            .flat_map(|variant| variant.fields.iter())
+            .collect()
     }
 }
 
@@ -0,0 +1,415 @@
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use syn::{parse::ParseBuffer, Error, Ident};
+
+use crate::{
+    idl,
+    moebius_instruction::MoebiusInstruction,
+    moebius_state::MoebiusState,
+    sol_type::{FieldAttrs, SolType},
+};
+
+pub struct MoebiusProgram {
+    state: MoebiusState,
+    instruction: MoebiusInstruction,
+}
+
+impl MoebiusProgram {
+    pub fn expand(&self) -> Result<TokenStream2, Error> {
+        let instruction = self.instruction.expand()?;
+
+        let state_ident = self.state.ident();
+        let instruction_ident = self.instruction.ident();
+        // `AnotherState` reports `AnotherError`s.
+        let state_name = state_ident.to_string();
+        let error_name = format!("{}Error", state_name.trim_end_matches("State"));
+        let error_ident = Ident::new(&error_name, Span::call_site());
+
+        // instruction fields are written to the state field of the same name.
+        let typed = self.state.typed()?;
+        let mut state_fields = vec![];
+        for field in self.state.fields() {
+            state_fields.push((
+                field.ident.clone().unwrap(),
+                SolType::parse_field(field)?,
+                FieldAttrs::parse(&field.attrs)?,
+            ));
+        }
+        let mut variants_fields = vec![];
+        let mut variants_sol_fields = vec![];
+        for variant_name in &["Initialize", "UpdateState"] {
+            let mut fields_ident = vec![];
+            let mut fields_ty = vec![];
+            // the fields as the state holds them
+            let mut fields_value = vec![];
+            let mut sol_fields = vec![];
+            // `check_eq` fields of `UpdateState` are compared instead of written
+            let mut fields_checked = vec![];
+            for field in self.instruction.variant_fields(variant_name) {
+                let field_ident = field.ident.clone().unwrap();
+                let sol_type = SolType::parse_field(field)?;
+                match state_fields
+                    .iter()
+                    .find(|(ident, _, _)| *ident == field_ident)
+                {
+                    Some((_, state_type, attrs)) if *state_type == sol_type => {
+                        if *variant_name == "UpdateState" && !attrs.is_updated() {
+                            let attr = if attrs.local.is_some() {
+                                "local"
+                            } else {
+                                "init_only"
+                            };
+                            return Err(Error::new_spanned(
+                                &field_ident,
+                                format!(
+                                    "\"{}\" is {}, so UpdateState can't carry it",
+                                    field_ident, attr
+                                ),
+                            ));
+                        }
+                        fields_checked
+                            .push(*variant_name == "UpdateState" && attrs.check_eq.is_some());
+                    }
+                    Some(_) => {
+                        return Err(Error::new_spanned(
+                            &field.ty,
+                            format!("the type of \"{}\" differs from the state's", field_ident),
+                        ))
+                    }
+                    None => {
+                        return Err(Error::new_spanned(
+                            &field_ident,
+                            format!("\"{}\" is not a field of {}", field_ident, state_ident),
+                        ))
+                    }
+                }
+                fields_value.push(match sol_type.typed_rust_type().filter(|_| typed) {
+                    Some(_) => sol_type.typed_value(&quote! { #field_ident }),
+                    None => quote! { #field_ident },
+                });
+                fields_ty.push(sol_type.rust_type());
+                sol_fields.push((field_ident.to_string(), sol_type));
+                fields_ident.push(field_ident);
+            }
+            variants_fields.push((fields_ident, fields_ty, fields_value, fields_checked));
+            variants_sol_fields.push(sol_fields);
+        }
+        // `UpdateState` only compares `check_eq` fields, so `Initialize` is what sets them.
+        for (ident, _, attrs) in &state_fields {
+            if attrs.check_eq.is_none() {
+                continue;
+            }
+            for (variant_name, sol_fields) in ["Initialize", "UpdateState"]
+                .iter()
+                .zip(&variants_sol_fields)
+            {
+                if !sol_fields.iter().any(|(name, _)| ident == name) {
+                    return Err(Error::new_spanned(
+                        ident,
+                        format!(
+                            "\"{}\" is check_eq, so {} must carry it",
+                            ident, variant_name
+                        ),
+                    ));
+                }
+            }
+        }
+        let (initialize_fields, initialize_fields_ty, initialize_values, _) = &variants_fields[0];
+        let (update_state_fields, update_state_fields_ty, update_state_values, fields_checked) =
+            &variants_fields[1];
+        let mut checked_fields = vec![];
+        let mut checked_values = vec![];
+        let mut written_fields = vec![];
+        let mut written_values = vec![];
+        for ((field, value), checked) in update_state_fields
+            .iter()
+            .zip(update_state_values)
+            .zip(fields_checked)
+        {
+            if *checked {
+                checked_fields.push(field);
+                checked_values.push(value);
+            } else {
+                written_fields.push(field);
+                written_values.push(value);
+            }
+        }
+
+        // the encoder follows `UpdateState`, which decodes its fields in its own order.
+        let state = self
+            .state
+            .expand_with_encoder(Some(&variants_sol_fields[1]))?;
+
+        let state_sol_fields: Vec<_> = state_fields
+            .iter()
+            .map(|(ident, sol_type, attrs)| (ident.to_string(), sol_type.clone(), attrs.clone()))
+            .collect();
+        let idl = idl::program(
+            &state_name,
+            &state_sol_fields,
+            &instruction_ident.to_string(),
+            &variants_sol_fields[0],
+            &variants_sol_fields[1],
+        );
+
+        Ok(quote! {
+            /// The JSON IDL of the program: the state layout, and the layout and accounts of
+            /// each instruction.
+            pub const IDL: &str = #idl;
+
+            pub mod error {
+                //! Error types
+
+                use solana_program::{
+                    decode_error::DecodeError,
+                    info,
+                    program_error::{PrintProgramError, ProgramError},
+                };
+
+                /// Errors that may be returned by the program.
+                #[derive(Clone, Debug, Eq, thiserror::Error, num_derive::FromPrimitive, PartialEq)]
+                pub enum #error_ident {
+                    /// Invalid instruction.
+                    #[error("Invalid instruction")]
+                    InvalidInstruction,
+                    /// The account cannot be initialized because it is already being used.
+                    #[error("Already in use")]
+                    AlreadyInUse,
+                    /// Lamport balance below rent-exempt threshold.
+                    #[error("Lamport balance below rent-exempt threshold")]
+                    NotRentExempt,
+                    /// The sender is not authorized to do the transaction.
+                    #[error("Unauthorized")]
+                    Unauthorized,
+                    /// A `check_eq` field does not match the stored value.
+                    #[error("Invalid account")]
+                    InvalidAccount,
+                }
+
+                impl From<#error_ident> for ProgramError {
+                    fn from(e: #error_ident) -> Self {
+                        ProgramError::Custom(e as u32)
+                    }
+                }
+
+                impl<T> DecodeError<T> for #error_ident {
+                    fn type_of() -> &'static str {
+                        #error_name
+                    }
+                }
+
+                impl PrintProgramError for #error_ident {
+                    fn print<E>(&self)
+                    where
+                        E: 'static
+                            + std::error::Error
+                            + DecodeError<E>
+                            + PrintProgramError
+                            + num_traits::FromPrimitive,
+                    {
+                        match self {
+                            #error_ident::InvalidInstruction => info!("Error: Invalid instruction"),
+                            #error_ident::AlreadyInUse => {
+                                info!("Error: account or token already in use")
+                            }
+                            #error_ident::NotRentExempt => {
+                                info!("Error: Lamport balance below rent-exempt threshold")
+                            }
+                            #error_ident::Unauthorized => {
+                                info!("Error: Account not authorized to do the transaction")
+                            }
+                            #error_ident::InvalidAccount => {
+                                info!("Error: Invalid account cannot be updated")
+                            }
+                        }
+                    }
+                }
+            }
+
+            pub mod state {
+                //! State types
+
+                use super::*;
+                use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
+                use solana_program::{
+                    program_error::ProgramError,
+                    program_pack::{IsInitialized, Pack, Sealed},
+                    pubkey::Pubkey,
+                };
+
+                #state
+            }
+
+            pub mod instruction {
+                //! Instruction types
+
+                use super::*;
+                use super::error::#error_ident::*;
+                use solana_program::{program_error::ProgramError, pubkey::Pubkey};
+                use std::mem::size_of;
+
+                #instruction
+            }
+
+            pub mod processor {
+                //! Program state processor
+
+                use super::*;
+                use super::{error::#error_ident, instruction::#instruction_ident, state::#state_ident};
+                use solana_program::{
+                    account_info::{next_account_info, AccountInfo},
+                    entrypoint::ProgramResult,
+                    info,
+                    program_error::ProgramError,
+                    program_pack::Pack,
+                    pubkey::Pubkey,
+                    sysvar::{rent::Rent, Sysvar},
+                };
+
+                /// Program state handler.
+                pub struct Processor {}
+                impl Processor {
+                    /// Processes an instruction.
+                    pub fn process(
+                        program_id: &Pubkey,
+                        accounts: &[AccountInfo],
+                        input: &[u8],
+                    ) -> ProgramResult {
+                        let instruction = #instruction_ident::unpack(input)?;
+
+                        match instruction {
+                            #instruction_ident::Initialize {
+                                moebius_program_id,
+                                #(#initialize_fields),*
+                            } => {
+                                info!("Instruction: Initialize");
+                                Self::process_initialize(
+                                    program_id,
+                                    accounts,
+                                    moebius_program_id,
+                                    #(#initialize_fields),*
+                                )
+                            }
+                            #instruction_ident::UpdateState { #(#update_state_fields),* } => {
+                                info!("Instruction: UpdateState");
+                                Self::process_update_state(accounts, #(#update_state_fields),*)
+                            }
+                        }
+                    }
+
+                    /// Processes an `Initialize` instruction.
+                    ///
+                    /// Accounts expected: the `[writable]` state account and the rent sysvar.
+                    #[allow(clippy::too_many_arguments)]
+                    pub fn process_initialize(
+                        program_id: &Pubkey,
+                        accounts: &[AccountInfo],
+                        moebius_program_id: Pubkey,
+                        #(#initialize_fields: #initialize_fields_ty),*
+                    ) -> ProgramResult {
+                        let account_info_iter = &mut accounts.iter();
+                        let state_account_info = next_account_info(account_info_iter)?;
+                        let rent = &Rent::from_account_info(next_account_info(account_info_iter)?)?;
+
+                        // Ensure that this account has not already been put into use.
+                        let state = #state_ident::unpack_unchecked(&state_account_info.data.borrow())?;
+                        if state.is_initialized {
+                            return Err(#error_ident::AlreadyInUse.into());
+                        }
+
+                        if !rent.is_exempt(state_account_info.lamports(), state_account_info.data_len()) {
+                            return Err(#error_ident::NotRentExempt.into());
+                        }
+
+                        // The program derived address Moebius signs the updates with.
+                        let (authority, _) = Pubkey::find_program_address(
+                            &[&program_id.to_bytes(), &state_account_info.key.to_bytes()],
+                            &moebius_program_id,
+                        );
+
+                        let mut state = #state_ident::default();
+                        state.is_initialized = true;
+                        state.authority = authority;
+                        #(
+                            state.#initialize_fields = #initialize_values;
+                        )*
+                        #state_ident::pack(state, &mut state_account_info.data.borrow_mut())?;
+
+                        Ok(())
+                    }
+
+                    /// Processes an `UpdateState` instruction.
+                    ///
+                    /// Accounts expected: the `[signer]` authority and the `[writable]` state
+                    /// account.
+                    #[allow(clippy::too_many_arguments)]
+                    pub fn process_update_state(
+                        accounts: &[AccountInfo],
+                        #(#update_state_fields: #update_state_fields_ty),*
+                    ) -> ProgramResult {
+                        let account_info_iter = &mut accounts.iter();
+                        let authority_info = next_account_info(account_info_iter)?;
+                        let state_account_info = next_account_info(account_info_iter)?;
+
+                        let mut state = #state_ident::unpack(&state_account_info.data.borrow())?;
+
+                        // The fields that identify the account must match.
+                        #(
+                            if state.#checked_fields != #checked_values {
+                                return Err(#error_ident::InvalidAccount.into());
+                            }
+                        )*
+
+                        if authority_info.key != &state.authority {
+                            return Err(#error_ident::Unauthorized.into());
+                        }
+                        if !authority_info.is_signer {
+                            return Err(ProgramError::MissingRequiredSignature);
+                        }
+
+                        #(
+                            state.#written_fields = #written_values;
+                        )*
+                        state.update_local_fields();
+                        #state_ident::pack(state, &mut state_account_info.data.borrow_mut())?;
+
+                        Ok(())
+                    }
+                }
+            }
+
+            #[cfg(not(feature = "no-entrypoint"))]
+            mod entrypoint {
+                //! Program entrypoint
+
+                use super::{error::#error_ident, processor::Processor};
+                use solana_program::{
+                    account_info::AccountInfo, entrypoint, entrypoint::ProgramResult,
+                    program_error::PrintProgramError, pubkey::Pubkey,
+                };
+
+                entrypoint!(process_instruction);
+                fn process_instruction<'a>(
+                    program_id: &Pubkey,
+                    accounts: &'a [AccountInfo<'a>],
+                    instruction_data: &[u8],
+                ) -> ProgramResult {
+                    if let Err(error) = Processor::process(program_id, accounts, instruction_data) {
+                        // catch the error so we can print it
+                        error.print::<#error_ident>();
+                        return Err(error);
+                    }
+                    Ok(())
+                }
+            }
+        })
+    }
+}
+
+impl syn::parse::Parse for MoebiusProgram {
+    fn parse(input: &ParseBuffer) -> Result<Self, Error> {
+        Ok(Self {
+            state: input.parse()?,
+            instruction: input.parse()?,
+        })
+    }
+}
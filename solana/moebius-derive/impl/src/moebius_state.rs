@@ -1,111 +1,208 @@
 use proc_macro2::{Span, TokenStream as TokenStream2};
-use quote::quote;
-use syn::{parse::ParseBuffer, Error, Fields, Ident, ItemStruct, Type};
+use quote::ToTokens;
+use syn::{parse::ParseBuffer, Error, Field, Fields, Ident, ItemStruct, Meta, NestedMeta};
 
-const SUPPORTED_TYPES_MSG: &str = "Types supported: \"bytes32\", \"address\", \"uint256\"";
+use crate::{
+    idl,
+    sol_type::{passthrough_attrs, FieldAttrs, SolType},
+    solidity,
+};
 
 pub struct MoebiusState {
     ast: ItemStruct,
 }
 
 impl MoebiusState {
-    pub fn expand(&self) -> TokenStream2 {
+    pub fn expand(&self) -> Result<TokenStream2, Error> {
+        self.expand_with_encoder(None)
+    }
+
+    /// Expands the state, with a Solidity encoder for `encoder_fields` if given, or else for
+    /// the fields `UpdateState` may carry, in state order.
+    pub fn expand_with_encoder(
+        &self,
+        encoder_fields: Option<&[(String, SolType)]>,
+    ) -> Result<TokenStream2, Error> {
+        if !self.ast.generics.params.is_empty() {
+            return Err(Error::new_spanned(
+                &self.ast.generics,
+                "moebius_state does not support generics",
+            ));
+        }
+
         // default fields
         // 1. whether the state is initialized or not
         // 2. the authority allowed to update the state
         let mut fields_ident = vec![
-            Ident::new("is_initialized", Span::call_site()),
-            Ident::new("authority", Span::call_site()),
+            Ident::new("is_initialized", Span::mixed_site()),
+            Ident::new("authority", Span::mixed_site()),
         ];
         let mut fields_ident_dst = vec![
-            Ident::new("is_initialized_dst", Span::call_site()),
-            Ident::new("authority_dst", Span::call_site()),
+            Ident::new("is_initialized_dst", Span::mixed_site()),
+            Ident::new("authority_dst", Span::mixed_site()),
         ];
         let mut fields_ident_src = vec![
-            Ident::new("is_initialized_src", Span::call_site()),
-            Ident::new("authority_src", Span::call_site()),
+            Ident::new("is_initialized_src", Span::mixed_site()),
+            Ident::new("authority_src", Span::mixed_site()),
         ];
         let mut pack_instructions = vec![
-            quote! { is_initialized_dst[0] = *is_initialized as u8 },
-            quote! { authority_dst.copy_from_slice(authority.as_ref()) },
+            quote! { is_initialized_dst[0] = *is_initialized as u8; },
+            quote! { authority_dst.copy_from_slice(authority.as_ref()); },
         ];
+        let mut check_len_instructions = vec![];
         let mut unpack_instructions = vec![
-            quote! { let is_initialized = is_initialized_src[0] == 1 },
-            quote! { let authority = Pubkey::new_from_array(*authority_src) },
+            quote! { let is_initialized = is_initialized_src[0] == 1; },
+            quote! { let authority = Pubkey::new_from_array(*authority_src); },
         ];
         let mut fields_ty = vec![quote! { bool }, quote! { Pubkey }];
+        let mut fields_default = vec![quote! { false }, quote! { Pubkey::default() }];
+        let mut fields_vis = vec![quote! { pub }, quote! { pub }];
+        let mut fields_attrs = vec![
+            quote! { #[doc = "Whether the state is initialized."] },
+            quote! { #[doc = "The authority allowed to update the state."] },
+        ];
         let mut fields_size = vec![1usize, 32usize];
         let mut state_size: usize = 33;
+        // dynamic fields are held in a `Vec` or a `String`
+        let mut is_copy = true;
+        let typed = self.typed()?;
+        // the fields `UpdateState` may carry, for the Solidity encoder
+        let mut sol_fields = vec![];
+        let mut idl_fields = vec![];
+        // the `local` fields set by a function after each update
+        let mut local_fields = vec![];
+        let mut local_updates = vec![];
 
-        match &self.ast.fields {
-            Fields::Named(fields_named) => {
-                for field in fields_named.named.iter() {
-                    if let Type::Path(ref p) = field.ty {
-                        let field_ident = field.ident.clone().unwrap();
-                        fields_ident.push(field_ident.clone());
-
-                        let field_dst_name = format!("{}_dst", field_ident.to_string());
-                        let field_ident_dst = Ident::new(&field_dst_name, Span::call_site());
-                        fields_ident_dst.push(field_ident_dst.clone());
-
-                        let field_src_name = format!("{}_src", field_ident.to_string());
-                        let field_ident_src = Ident::new(&field_src_name, Span::call_site());
-                        fields_ident_src.push(field_ident_src.clone());
-
-                        let input_ty = p.path.segments[0].ident.to_string();
-                        let field_ty = match input_ty.as_ref() {
-                            "address" => {
-                                state_size += 20;
-                                fields_size.push(20);
-                                pack_instructions.push(
-                                    quote! { #field_ident_dst.copy_from_slice(&#field_ident[..]) },
-                                );
-                                unpack_instructions
-                                    .push(quote! { let #field_ident = *#field_ident_src });
-                                quote! { [u8; 20] }
-                            }
-                            "bytes32" | "uint256" => {
-                                state_size += 32;
-                                fields_size.push(32);
-                                pack_instructions.push(
-                                    quote! { #field_ident_dst.copy_from_slice(&#field_ident[..]) },
-                                );
-                                unpack_instructions
-                                    .push(quote! { let #field_ident = *#field_ident_src });
-                                quote! { [u8; 32] }
-                            }
-                            "bool" | "uint8" => {
-                                state_size += 1;
-                                fields_size.push(1);
-                                pack_instructions
-                                    .push(quote! { #field_ident_dst[0] = *#field_ident });
-                                unpack_instructions
-                                    .push(quote! { let #field_ident = #field_ident_src[0] });
-                                quote! { u8 }
-                            }
-                            _ => panic!(format!(
-                                "Unexpected type: \"{}\"\n{}",
-                                input_ty, SUPPORTED_TYPES_MSG
-                            )),
-                        };
-                        fields_ty.push(field_ty);
-                    }
-                }
+        let fields_named = match &self.ast.fields {
+            Fields::Named(fields_named) => fields_named,
+            fields => {
+                return Err(Error::new_spanned(
+                    fields,
+                    "moebius_state expects a struct with named fields",
+                ))
             }
-            _ => {}
         };
+        for field in fields_named.named.iter() {
+            let field_ident = field.ident.clone().unwrap();
+            if fields_ident.contains(&field_ident) {
+                return Err(Error::new_spanned(
+                    &field_ident,
+                    format!("\"{}\" is a field moebius_state adds itself", field_ident),
+                ));
+            }
+            fields_ident.push(field_ident.clone());
+
+            let field_dst_name = format!("{}_dst", field_ident.to_string());
+            let field_ident_dst = Ident::new(&field_dst_name, Span::mixed_site());
+            fields_ident_dst.push(field_ident_dst.clone());
+
+            let field_src_name = format!("{}_src", field_ident.to_string());
+            let field_ident_src = Ident::new(&field_src_name, Span::mixed_site());
+            fields_ident_src.push(field_ident_src.clone());
+
+            let sol_type = SolType::parse_field(field)?;
+            is_copy &= !sol_type.is_dynamic();
+            state_size += sol_type.packed_size();
+            fields_size.push(sol_type.packed_size());
+            check_len_instructions.push(sol_type.check_len(
+                &quote! { src.#field_ident },
+                &quote! { ProgramError::InvalidAccountData },
+            ));
+            let mut pack = sol_type.pack(&quote! { #field_ident }, &quote! { #field_ident_dst });
+            let mut unpack = sol_type.unpack(
+                &quote! { #field_ident_src },
+                &quote! { ProgramError::InvalidAccountData },
+            );
+            match sol_type.typed_rust_type().filter(|_| typed) {
+                Some(typed_rust_type) => {
+                    // packed as the untyped value
+                    let untyped = sol_type.untyped_value(&quote! { *#field_ident });
+                    let pack_untyped =
+                        sol_type.pack(&quote! { untyped }, &quote! { #field_ident_dst });
+                    pack = quote! {{
+                        let untyped = #untyped;
+                        #pack_untyped
+                    }};
+                    unpack = sol_type.typed_value(&unpack);
+                    fields_ty.push(typed_rust_type);
+                }
+                None => fields_ty.push(sol_type.rust_type()),
+            }
+            pack_instructions.push(pack);
+            unpack_instructions.push(quote! { let #field_ident = #unpack; });
+            fields_default.push(sol_type.default_value());
+            fields_vis.push(field.vis.to_token_stream());
+            fields_attrs.push(passthrough_attrs(&field.attrs));
+            let field_attrs = FieldAttrs::parse(&field.attrs)?;
+            if field_attrs.is_updated() {
+                sol_fields.push((field_ident.to_string(), sol_type.clone()));
+            }
+            if let Some(local_update) = &field_attrs.local_update {
+                local_fields.push(field_ident.clone());
+                local_updates.push(local_update.clone());
+            }
+            idl_fields.push((field_ident.to_string(), sol_type, field_attrs));
+        }
 
+        let attrs = passthrough_attrs(&self.ast.attrs);
         let vis = &self.ast.vis;
         let ident = &self.ast.ident;
+        let encoder_doc = match encoder_fields {
+            Some(_) => {
+                " A Solidity library whose `pack` function encodes the fields of `UpdateState`, \
+                 in order, as its `_packedData`."
+            }
+            None => {
+                " A Solidity library whose `pack` function encodes the fields, in order, as the \
+                 `_packedData` of an `UpdateState` instruction. `local` and `init_only` fields \
+                 are left out, unless they are `check_eq`."
+            }
+        };
+        let encoder_fields = encoder_fields.unwrap_or(&sol_fields);
+        let solidity_encoder = solidity::library(&ident.to_string(), encoder_fields);
+        let encoder_abi = solidity::abi_json();
+        let idl = idl::state(&ident.to_string(), &idl_fields);
+        let copy = if is_copy {
+            quote! { Copy, }
+        } else {
+            quote! {}
+        };
 
-        quote! {
+        Ok(quote! {
+            #attrs
             #[repr(C)]
-            #[derive(Clone, Copy, Debug, Default, PartialEq)]
+            #[derive(Clone, #copy Debug, PartialEq)]
             #vis struct #ident {
                 #(
-                    #fields_ident: #fields_ty
+                    #fields_attrs
+                    #fields_vis #fields_ident: #fields_ty
                 ),*
             }
+            impl #ident {
+                #[doc = #encoder_doc]
+                pub const SOLIDITY_ENCODER: &str = #solidity_encoder;
+                /// The ABI JSON of the `SOLIDITY_ENCODER` library, as solc emits it.
+                pub const ENCODER_ABI: &str = #encoder_abi;
+                /// The JSON IDL of the state: the offset, size and type of each field.
+                pub const IDL: &str = #idl;
+
+                /// Sets the `local` fields that have an update function, in order, from the
+                /// state `UpdateState` wrote the other fields to.
+                pub fn update_local_fields(&mut self) {
+                    #(
+                        self.#local_fields = #local_updates(self);
+                    )*
+                }
+            }
+            impl Default for #ident {
+                fn default() -> Self {
+                    Self {
+                        #(
+                            #fields_ident: #fields_default
+                        ),*
+                    }
+                }
+            }
             impl IsInitialized for #ident {
                 fn is_initialized(&self) -> bool {
                     self.is_initialized
@@ -114,6 +211,16 @@ impl MoebiusState {
             impl Sealed for #ident {}
             impl Pack for #ident {
                 const LEN: usize = #state_size;
+                fn pack(src: Self, dst: &mut [u8]) -> Result<(), ProgramError> {
+                    if dst.len() != Self::LEN {
+                        return Err(ProgramError::InvalidAccountData);
+                    }
+                    #(
+                        #check_len_instructions
+                    )*
+                    src.pack_into_slice(dst);
+                    Ok(())
+                }
                 fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
                     let src = array_ref![src, 0, #state_size];
                     let (
@@ -128,7 +235,7 @@ impl MoebiusState {
                     ];
                     #(
                         #unpack_instructions
-                    );*;
+                    )*
                     Ok(#ident {
                         #(
                             #fields_ident
@@ -154,10 +261,46 @@ impl MoebiusState {
                     } = self;
                     #(
                         #pack_instructions
-                    );*;
+                    )*
+                }
+            }
+        })
+    }
+}
+
+impl MoebiusState {
+    /// Whether the struct has `#[moebius(typed)]`, to hold `uint136` to `uint256` fields as a
+    /// `U256` and `address` fields as an `Address`.
+    pub fn typed(&self) -> Result<bool, Error> {
+        let mut typed = false;
+        for attr in self
+            .ast
+            .attrs
+            .iter()
+            .filter(|attr| attr.path.is_ident("moebius"))
+        {
+            match attr.parse_meta()? {
+                Meta::List(list)
+                    if list.nested.iter().all(|nested| {
+                        matches!(nested, NestedMeta::Meta(Meta::Path(path)) if path.is_ident("typed"))
+                    }) =>
+                {
+                    typed = true;
                 }
+                meta => return Err(Error::new_spanned(meta, "expected #[moebius(typed)]")),
             }
         }
+
+        Ok(typed)
+    }
+
+    pub fn ident(&self) -> &Ident {
+        &self.ast.ident
+    }
+
+    /// The fields declared on the struct, without the ones `expand` adds.
+    pub fn fields(&self) -> Vec<&Field> {
+        self.ast.fields.iter().collect()
     }
 }
 
@@ -0,0 +1,775 @@
+use proc_macro2::TokenStream as TokenStream2;
+use syn::{
+    Attribute, Error, Expr, ExprLit, Field, Lit, LitInt, Meta, NestedMeta, Path, Result, Type,
+};
+
+pub const SUPPORTED_TYPES_MSG: &str =
+    "Types supported: \"address\", \"bool\", \"uint8\" to \"uint256\", \
+     \"int8\" to \"int256\", \"bytes1\" to \"bytes32\", fixed-size arrays of those, e.g. \
+     \"[uint256; 4]\", and with #[moebius(max_len = N)], \"bytes\", \"string\" and \
+     dynamic arrays, e.g. \"[uint256]\"";
+
+/// The Solidity type of a field in `moebius_state` or `moebius_instruction`.
+///
+/// Integers that fit a Rust integer are held as one (`uint40` to `uint64` as `u64`, and so on)
+/// and packed little-endian in the account. Wider integers are held as the big-endian 32-byte
+/// ABI word, two's complement for `intN`.
+///
+/// Dynamic types are bounded by the field's `#[moebius(max_len = N)]`, and take a `u32` length
+/// followed by room for `N` elements in the account.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SolType {
+    Address,
+    Bool,
+    /// `uintN`, with the number of bits.
+    Uint(usize),
+    /// `intN`, with the number of bits.
+    Int(usize),
+    /// `bytesN`, with the number of bytes.
+    FixedBytes(usize),
+    /// `T[N]`, written `[T; N]` since the field has to be valid Rust.
+    FixedArray(Box<SolType>, usize),
+    /// `bytes`, with the maximum number of bytes.
+    Bytes(usize),
+    /// `string`, with the maximum number of bytes.
+    String(usize),
+    /// `T[]`, written `[T]`, with the maximum number of elements.
+    Array(Box<SolType>, usize),
+}
+
+impl SolType {
+    /// Parses the type of a field, along with its `#[moebius(max_len = N)]` attribute.
+    pub fn parse_field(field: &Field) -> Result<SolType> {
+        let max_len = FieldAttrs::parse(&field.attrs)?.max_len;
+        let sol_type = Self::parse(&field.ty, max_len.as_ref().map(|(max_len, _)| *max_len))?;
+        match max_len {
+            Some((_, lit)) if !sol_type.is_dynamic() => Err(Error::new_spanned(
+                lit,
+                "max_len only applies to \"bytes\", \"string\" and dynamic arrays",
+            )),
+            _ => Ok(sol_type),
+        }
+    }
+
+    fn parse(ty: &Type, max_len: Option<usize>) -> Result<SolType> {
+        let unsupported = || {
+            Error::new_spanned(
+                ty,
+                format!(
+                    "Unexpected type: \"{}\"\n{}",
+                    quote! { #ty },
+                    SUPPORTED_TYPES_MSG
+                ),
+            )
+        };
+        let required_max_len = |name: &str| {
+            max_len.ok_or_else(|| {
+                Error::new_spanned(
+                    ty,
+                    format!("\"{}\" needs a #[moebius(max_len = N)] bound", name),
+                )
+            })
+        };
+        // elements of arrays are held inline, so they can't be dynamic themselves. Any bound
+        // lets dynamic elements parse, to be reported as such.
+        let static_elem = |elem: &Type| {
+            let sol_type = Self::parse(elem, Some(0))?;
+            if sol_type.is_dynamic() {
+                Err(Error::new_spanned(
+                    elem,
+                    "arrays of dynamic types are not supported",
+                ))
+            } else {
+                Ok(Box::new(sol_type))
+            }
+        };
+
+        match ty {
+            Type::Path(p) if p.qself.is_none() => {
+                let name = p.path.get_ident().ok_or_else(unsupported)?.to_string();
+                match name.as_ref() {
+                    "bytes" => Ok(SolType::Bytes(required_max_len("bytes")?)),
+                    "string" => Ok(SolType::String(required_max_len("string")?)),
+                    _ => Self::parse_elementary(&name).ok_or_else(unsupported),
+                }
+            }
+            Type::Array(a) => match &a.len {
+                Expr::Lit(ExprLit {
+                    lit: Lit::Int(len), ..
+                }) => {
+                    let len = len
+                        .base10_parse::<usize>()
+                        .ok()
+                        .filter(|len| *len > 0)
+                        .ok_or_else(|| Error::new_spanned(len, "expected a positive length"))?;
+                    Ok(SolType::FixedArray(static_elem(&a.elem)?, len))
+                }
+                len => Err(Error::new_spanned(len, "expected a literal length")),
+            },
+            Type::Slice(s) => {
+                let elem = static_elem(&s.elem)?;
+                Ok(SolType::Array(elem, required_max_len("dynamic array")?))
+            }
+            _ => Err(unsupported()),
+        }
+    }
+
+    fn parse_elementary(name: &str) -> Option<SolType> {
+        // `uint` and `int` are aliases for `uint256` and `int256`.
+        let bits = |suffix: &str| match suffix {
+            "" => Some(256),
+            _ => parse_size(suffix).filter(|bits| *bits <= 256 && bits % 8 == 0),
+        };
+
+        match name {
+            "address" => Some(SolType::Address),
+            "bool" => Some(SolType::Bool),
+            _ if name.starts_with("uint") => bits(&name[4..]).map(SolType::Uint),
+            _ if name.starts_with("int") => bits(&name[3..]).map(SolType::Int),
+            _ if name.starts_with("bytes") => parse_size(&name[5..])
+                .filter(|len| *len <= 32)
+                .map(SolType::FixedBytes),
+            _ => None,
+        }
+    }
+
+    /// Whether the type is ABI-encoded out of place, behind an offset.
+    pub fn is_dynamic(&self) -> bool {
+        matches!(
+            self,
+            SolType::Bytes(_) | SolType::String(_) | SolType::Array(..)
+        )
+    }
+
+    /// The canonical Solidity name of the type, e.g. `uint256[4]`.
+    pub fn sol_name(&self) -> String {
+        match self {
+            SolType::Address => "address".to_string(),
+            SolType::Bool => "bool".to_string(),
+            SolType::Uint(bits) => format!("uint{}", bits),
+            SolType::Int(bits) => format!("int{}", bits),
+            SolType::FixedBytes(len) => format!("bytes{}", len),
+            SolType::FixedArray(elem, len) => format!("{}[{}]", elem.sol_name(), len),
+            SolType::Bytes(_) => "bytes".to_string(),
+            SolType::String(_) => "string".to_string(),
+            SolType::Array(elem, _) => format!("{}[]", elem.sol_name()),
+        }
+    }
+
+    /// The Rust type the field is held as.
+    pub fn rust_type(&self) -> TokenStream2 {
+        match self {
+            SolType::Address => quote! { [u8; 20] },
+            SolType::Bool => quote! { u8 },
+            SolType::Uint(_) | SolType::Int(_) => match self.native_size() {
+                Some(size) => self.native_type(size),
+                None => quote! { [u8; 32] },
+            },
+            SolType::FixedBytes(len) => quote! { [u8; #len] },
+            SolType::FixedArray(elem, len) => {
+                let elem = elem.rust_type();
+                quote! { [#elem; #len] }
+            }
+            SolType::Bytes(_) => quote! { Vec<u8> },
+            SolType::String(_) => quote! { String },
+            SolType::Array(elem, _) => {
+                let elem = elem.rust_type();
+                quote! { Vec<#elem> }
+            }
+        }
+    }
+
+    /// An expression for the default value of the field. Arrays only implement `Default` up to
+    /// 32 elements, so they are built from the default of their elements.
+    pub fn default_value(&self) -> TokenStream2 {
+        match self {
+            SolType::FixedArray(elem, len) => {
+                let elem = elem.default_value();
+                quote! { [#elem; #len] }
+            }
+            _ => quote! { Default::default() },
+        }
+    }
+
+    /// The type a `#[moebius(typed)]` state holds the field as, when it differs from
+    /// `rust_type`. Arrays hold their elements typed.
+    pub fn typed_rust_type(&self) -> Option<TokenStream2> {
+        match self {
+            SolType::Address => Some(quote! { moebius_derive::types::Address }),
+            SolType::Uint(_) if self.native_size().is_none() => {
+                Some(quote! { moebius_derive::types::U256 })
+            }
+            SolType::FixedArray(elem, len) => {
+                elem.typed_rust_type().map(|elem| quote! { [#elem; #len] })
+            }
+            SolType::Array(elem, _) => elem.typed_rust_type().map(|elem| quote! { Vec<#elem> }),
+            _ => None,
+        }
+    }
+
+    /// Converts `value`, held as `rust_type`, to `typed_rust_type`.
+    pub fn typed_value(&self, value: &TokenStream2) -> TokenStream2 {
+        self.convert(value, true)
+    }
+
+    /// Converts `value`, held as `typed_rust_type`, back to `rust_type`.
+    pub fn untyped_value(&self, value: &TokenStream2) -> TokenStream2 {
+        self.convert(value, false)
+    }
+
+    fn convert(&self, value: &TokenStream2, typed: bool) -> TokenStream2 {
+        match self {
+            SolType::FixedArray(elem, _) => {
+                let convert_elem = elem.convert(&quote! { *elem }, typed);
+                let default = self.default_value();
+                quote! {{
+                    let mut converted = #default;
+                    for (converted, elem) in converted.iter_mut().zip((#value).iter()) {
+                        *converted = #convert_elem;
+                    }
+                    converted
+                }}
+            }
+            SolType::Array(elem, _) => {
+                let convert_elem = elem.convert(&quote! { *elem }, typed);
+                quote! { (#value).iter().map(|elem| #convert_elem).collect::<Vec<_>>() }
+            }
+            _ => {
+                let ty = if typed {
+                    self.typed_rust_type()
+                } else {
+                    Some(self.rust_type())
+                };
+                quote! { <#ty>::from(#value) }
+            }
+        }
+    }
+
+    /// The number of bytes the field takes in an account, or in an `Initialize` instruction.
+    pub fn packed_size(&self) -> usize {
+        match self {
+            SolType::Address => 20,
+            SolType::Bool => 1,
+            SolType::Uint(_) | SolType::Int(_) => self.native_size().unwrap_or(32),
+            SolType::FixedBytes(len) => *len,
+            SolType::FixedArray(elem, len) => elem.packed_size() * len,
+            SolType::Bytes(max_len) | SolType::String(max_len) => 4 + max_len,
+            SolType::Array(elem, max_len) => 4 + elem.packed_size() * max_len,
+        }
+    }
+
+    /// The number of bytes the field takes once ABI-encoded, in the head for dynamic types.
+    pub fn abi_size(&self) -> usize {
+        match self {
+            SolType::FixedArray(elem, len) => elem.abi_size() * len,
+            _ => 32,
+        }
+    }
+
+    /// Returns `err` from the enclosing function if `value`, a dynamic value, is longer than
+    /// its `max_len`. Checks nothing for the other types.
+    pub fn check_len(&self, value: &TokenStream2, err: &TokenStream2) -> TokenStream2 {
+        match self {
+            SolType::Bytes(max_len) | SolType::String(max_len) | SolType::Array(_, max_len) => {
+                quote! {
+                    if #value.len() > #max_len {
+                        return Err(#err);
+                    }
+                }
+            }
+            _ => quote! {},
+        }
+    }
+
+    /// Writes `value`, a reference to the field, to `dst`, a slice of `packed_size` bytes.
+    /// Dynamic values must have been checked with `check_len` first.
+    pub fn pack(&self, value: &TokenStream2, dst: &TokenStream2) -> TokenStream2 {
+        match self {
+            SolType::Bool => quote! { #dst[0] = *#value; },
+            SolType::Uint(_) | SolType::Int(_) if self.native_size().is_some() => {
+                quote! { #dst.copy_from_slice(&#value.to_le_bytes()); }
+            }
+            SolType::FixedArray(elem, _) => {
+                let size = elem.packed_size();
+                let pack_elem = elem.pack(&quote! { elem }, &quote! { elem_dst });
+                quote! {
+                    for (i, elem) in #value.iter().enumerate() {
+                        let elem_dst = &mut #dst[i * #size..(i + 1) * #size];
+                        #pack_elem
+                    }
+                }
+            }
+            SolType::Bytes(_) | SolType::String(_) => quote! {
+                let bytes: &[u8] = #value.as_ref();
+                #dst[..4].copy_from_slice(&(bytes.len() as u32).to_le_bytes());
+                #dst[4..4 + bytes.len()].copy_from_slice(bytes);
+                for b in #dst[4 + bytes.len()..].iter_mut() {
+                    *b = 0;
+                }
+            },
+            SolType::Array(elem, _) => {
+                let size = elem.packed_size();
+                let pack_elem = elem.pack(&quote! { elem }, &quote! { elem_dst });
+                quote! {
+                    #dst[..4].copy_from_slice(&(#value.len() as u32).to_le_bytes());
+                    for (i, elem) in #value.iter().enumerate() {
+                        let elem_dst = &mut #dst[4 + i * #size..4 + (i + 1) * #size];
+                        #pack_elem
+                    }
+                    for b in #dst[4 + #value.len() * #size..].iter_mut() {
+                        *b = 0;
+                    }
+                }
+            }
+            _ => quote! { #dst.copy_from_slice(&#value[..]); },
+        }
+    }
+
+    /// Reads the field back from `src`, a slice of `packed_size` bytes. `err` is the
+    /// `ProgramError` returned if a dynamic value is invalid.
+    pub fn unpack(&self, src: &TokenStream2, err: &TokenStream2) -> TokenStream2 {
+        match self {
+            SolType::Bool => quote! { #src[0] },
+            SolType::Bytes(_) | SolType::String(_) | SolType::Array(..) => {
+                let len = quote! {{
+                    let mut len = [0u8; 4];
+                    len.copy_from_slice(&#src[..4]);
+                    u32::from_le_bytes(len) as usize
+                }};
+                let value = self.unpack_contents(&quote! { contents }, err);
+                let (max_len, size) = self.dynamic_bounds();
+                quote! {{
+                    let len = #len;
+                    if len > #max_len {
+                        return Err(#err);
+                    }
+                    let contents = &#src[4..4 + len * #size];
+                    #value
+                }}
+            }
+            SolType::FixedArray(elem, len) => {
+                let size = elem.packed_size();
+                let zero = elem.zero();
+                let unpack_elem = elem.unpack(&quote! { elem_src }, err);
+                quote! {{
+                    let mut array = [#zero; #len];
+                    for (i, elem) in array.iter_mut().enumerate() {
+                        let elem_src = &#src[i * #size..(i + 1) * #size];
+                        *elem = #unpack_elem;
+                    }
+                    array
+                }}
+            }
+            _ => {
+                let size = self.packed_size();
+                let bytes = quote! {{
+                    let mut bytes = [0u8; #size];
+                    bytes.copy_from_slice(&#src[..]);
+                    bytes
+                }};
+                match self.native_size() {
+                    Some(size) => {
+                        let native = self.native_type(size);
+                        quote! { #native::from_le_bytes(#bytes) }
+                    }
+                    None => bytes,
+                }
+            }
+        }
+    }
+
+    /// Appends the ABI encoding of `value`, a reference to the field, to `buf`. For dynamic
+    /// types, this is the tail the head's offset points to.
+    pub fn encode_abi(&self, value: &TokenStream2) -> TokenStream2 {
+        match self {
+            SolType::Bytes(_) | SolType::String(_) => quote! {
+                let bytes: &[u8] = #value.as_ref();
+                buf.extend_from_slice(&[0u8; 28]);
+                buf.extend_from_slice(&(bytes.len() as u32).to_be_bytes());
+                buf.extend_from_slice(bytes);
+                buf.resize(buf.len() + (32 - bytes.len() % 32) % 32, 0);
+            },
+            SolType::Array(elem, _) => {
+                let encode_elem = elem.encode_abi(&quote! { elem });
+                quote! {
+                    buf.extend_from_slice(&[0u8; 28]);
+                    buf.extend_from_slice(&(#value.len() as u32).to_be_bytes());
+                    for elem in #value.iter() {
+                        #encode_elem
+                    }
+                }
+            }
+            SolType::Address => quote! {
+                buf.extend_from_slice(&[0u8; 12]);
+                buf.extend_from_slice(&#value[..]);
+            },
+            SolType::Bool => quote! {
+                buf.extend_from_slice(&[0u8; 31]);
+                buf.push(*#value);
+            },
+            SolType::Uint(_) | SolType::Int(_) => match self.native_size() {
+                Some(size) => {
+                    let pad = 32 - size;
+                    let pad_byte = match self {
+                        SolType::Int(_) => quote! { if *#value < 0 { 0xff } else { 0 } },
+                        _ => quote! { 0u8 },
+                    };
+                    quote! {
+                        buf.extend_from_slice(&[#pad_byte; #pad]);
+                        buf.extend_from_slice(&#value.to_be_bytes());
+                    }
+                }
+                None => quote! { buf.extend_from_slice(&#value[..]); },
+            },
+            SolType::FixedBytes(len) => {
+                let pad = 32 - len;
+                quote! {
+                    buf.extend_from_slice(&#value[..]);
+                    buf.extend_from_slice(&[0u8; #pad]);
+                }
+            }
+            SolType::FixedArray(elem, _) => {
+                let encode_elem = elem.encode_abi(&quote! { elem });
+                quote! {
+                    for elem in #value.iter() {
+                        #encode_elem
+                    }
+                }
+            }
+        }
+    }
+
+    /// Decodes the field from `src`, a slice of `abi_size` bytes, rejecting values that are out
+    /// of range for the type with `InvalidInstruction`. For dynamic types, `src` is the rest of
+    /// the encoding from the tail the head's offset points to.
+    pub fn decode_abi(&self, src: &TokenStream2) -> TokenStream2 {
+        match self {
+            SolType::Bytes(_) | SolType::String(_) | SolType::Array(..) => {
+                let len = decode_abi_usize(&quote! { #src[..32] });
+                let (max_len, _) = self.dynamic_bounds();
+                let (contents, value) = match self {
+                    SolType::Array(elem, _) => {
+                        let size = elem.abi_size();
+                        let decode_elem = elem.decode_abi(&quote! { elem_src });
+                        (
+                            quote! { #src[32..].get(..len * #size) },
+                            quote! {{
+                                let mut vec = Vec::with_capacity(len);
+                                for elem_src in contents.chunks(#size) {
+                                    vec.push(#decode_elem);
+                                }
+                                vec
+                            }},
+                        )
+                    }
+                    _ => (
+                        quote! { #src[32..].get(..len) },
+                        self.unpack_contents(
+                            &quote! { contents },
+                            &quote! { ProgramError::from(InvalidInstruction) },
+                        ),
+                    ),
+                };
+                quote! {{
+                    if #src.len() < 32 {
+                        return Err(InvalidInstruction.into());
+                    }
+                    let len = #len;
+                    if len > #max_len {
+                        return Err(InvalidInstruction.into());
+                    }
+                    let contents = #contents.ok_or(InvalidInstruction)?;
+                    #value
+                }}
+            }
+            SolType::Address => {
+                let check = check_padding(src, quote! { ..12 }, quote! { 0 });
+                quote! {{
+                    #check
+                    let mut bytes = [0u8; 20];
+                    bytes.copy_from_slice(&#src[12..]);
+                    bytes
+                }}
+            }
+            SolType::Bool => {
+                let check = check_padding(src, quote! { ..31 }, quote! { 0 });
+                quote! {{
+                    #check
+                    if #src[31] > 1 {
+                        return Err(InvalidInstruction.into());
+                    }
+                    #src[31]
+                }}
+            }
+            SolType::Uint(bits) | SolType::Int(bits) => {
+                let pad = 32 - bits / 8;
+                let native = self.native_size();
+                let value_start = 32 - native.unwrap_or(32);
+                let check = match self {
+                    _ if pad == 0 => quote! {},
+                    // the word is sign-extended from the first byte of the value.
+                    SolType::Int(_) => check_padding(
+                        src,
+                        quote! { ..#pad },
+                        quote! { if #src[#pad] & 0x80 == 0 { 0 } else { 0xff } },
+                    ),
+                    _ => check_padding(src, quote! { ..#pad }, quote! { 0 }),
+                };
+                let size = 32 - value_start;
+                let bytes = quote! {{
+                    let mut bytes = [0u8; #size];
+                    bytes.copy_from_slice(&#src[#value_start..]);
+                    bytes
+                }};
+                let value = match native {
+                    Some(size) => {
+                        let native = self.native_type(size);
+                        quote! { #native::from_be_bytes(#bytes) }
+                    }
+                    None => bytes,
+                };
+                quote! {{
+                    #check
+                    #value
+                }}
+            }
+            SolType::FixedBytes(len) => {
+                let check = match len {
+                    32 => quote! {},
+                    _ => check_padding(src, quote! { #len.. }, quote! { 0 }),
+                };
+                quote! {{
+                    #check
+                    let mut bytes = [0u8; #len];
+                    bytes.copy_from_slice(&#src[..#len]);
+                    bytes
+                }}
+            }
+            SolType::FixedArray(elem, len) => {
+                let size = elem.abi_size();
+                let zero = elem.zero();
+                let decode_elem = elem.decode_abi(&quote! { elem_src });
+                quote! {{
+                    let mut array = [#zero; #len];
+                    for (i, elem) in array.iter_mut().enumerate() {
+                        let elem_src = &#src[i * #size..(i + 1) * #size];
+                        *elem = #decode_elem;
+                    }
+                    array
+                }}
+            }
+        }
+    }
+
+    /// The maximum length of a dynamic type, and the size of its elements in an account.
+    fn dynamic_bounds(&self) -> (usize, usize) {
+        match self {
+            SolType::Bytes(max_len) | SolType::String(max_len) => (*max_len, 1),
+            SolType::Array(elem, max_len) => (*max_len, elem.packed_size()),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Builds a dynamic value from `contents`, its elements as laid out in an account.
+    fn unpack_contents(&self, contents: &TokenStream2, err: &TokenStream2) -> TokenStream2 {
+        match self {
+            SolType::Bytes(_) => quote! { #contents.to_vec() },
+            SolType::String(_) => quote! {
+                String::from_utf8(#contents.to_vec()).map_err(|_| #err)?
+            },
+            SolType::Array(elem, _) => {
+                let size = elem.packed_size();
+                let unpack_elem = elem.unpack(&quote! { elem_src }, err);
+                quote! {{
+                    let mut vec = Vec::with_capacity(#contents.len() / #size);
+                    for elem_src in #contents.chunks(#size) {
+                        vec.push(#unpack_elem);
+                    }
+                    vec
+                }}
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    /// The number of bytes of the Rust integer an integer type is held as, if any.
+    fn native_size(&self) -> Option<usize> {
+        match self {
+            SolType::Uint(bits) | SolType::Int(bits) => match bits {
+                8 => Some(1),
+                16 => Some(2),
+                24..=32 => Some(4),
+                40..=64 => Some(8),
+                72..=128 => Some(16),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn native_type(&self, size: usize) -> TokenStream2 {
+        let bits = size * 8;
+        match (self, bits) {
+            (SolType::Int(_), 8) => quote! { i8 },
+            (SolType::Int(_), 16) => quote! { i16 },
+            (SolType::Int(_), 32) => quote! { i32 },
+            (SolType::Int(_), 64) => quote! { i64 },
+            (SolType::Int(_), _) => quote! { i128 },
+            (_, 8) => quote! { u8 },
+            (_, 16) => quote! { u16 },
+            (_, 32) => quote! { u32 },
+            (_, 64) => quote! { u64 },
+            (_, _) => quote! { u128 },
+        }
+    }
+
+    /// An expression for the zero value of the Rust type, to initialize arrays with.
+    fn zero(&self) -> TokenStream2 {
+        match self {
+            SolType::FixedArray(elem, len) => {
+                let zero = elem.zero();
+                quote! { [#zero; #len] }
+            }
+            _ => match self.native_size() {
+                Some(_) => quote! { 0 },
+                None if *self == SolType::Bool => quote! { 0 },
+                None => {
+                    let size = self.packed_size();
+                    quote! { [0u8; #size] }
+                }
+            },
+        }
+    }
+}
+
+/// Parses the size suffix of `uintN`, `intN` or `bytesN`, which must be a plain positive number.
+fn parse_size(suffix: &str) -> Option<usize> {
+    suffix
+        .parse::<usize>()
+        .ok()
+        .filter(|size| *size > 0 && size.to_string() == suffix)
+}
+
+/// The attributes of a field to keep on the generated one: all but `#[moebius(..)]`.
+pub fn passthrough_attrs(attrs: &[Attribute]) -> TokenStream2 {
+    let attrs = attrs.iter().filter(|attr| !attr.path.is_ident("moebius"));
+    quote! { #(#attrs)* }
+}
+
+/// A field's `#[moebius(..)]` attributes.
+#[derive(Clone, Default)]
+pub struct FieldAttrs {
+    /// `max_len = N`, the bound of a dynamic type.
+    pub max_len: Option<(usize, LitInt)>,
+    /// `init_only`, set by `Initialize` and never by `UpdateState`.
+    pub init_only: Option<Path>,
+    /// `local`, a Solana-only field that is not sent from Ethereum.
+    pub local: Option<Path>,
+    /// `local = "update_fn"`, the function that sets a `local` field after each `UpdateState`.
+    pub local_update: Option<Path>,
+    /// `check_eq`, compared with the stored value by `UpdateState` instead of written.
+    pub check_eq: Option<Path>,
+}
+
+impl FieldAttrs {
+    pub fn parse(attrs: &[Attribute]) -> Result<FieldAttrs> {
+        let mut field_attrs = FieldAttrs::default();
+        for attr in attrs.iter().filter(|attr| attr.path.is_ident("moebius")) {
+            let nested = match attr.parse_meta()? {
+                Meta::List(list) => list.nested,
+                meta => {
+                    return Err(Error::new_spanned(
+                        meta,
+                        "expected #[moebius(max_len = N)], #[moebius(init_only)], \
+                         #[moebius(local)], #[moebius(local = \"update_fn\")] or \
+                         #[moebius(check_eq)]",
+                    ))
+                }
+            };
+            for meta in nested.iter() {
+                match meta {
+                    NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("max_len") => {
+                        let lit = match &nv.lit {
+                            Lit::Int(lit) => lit,
+                            lit => return Err(Error::new_spanned(lit, "expected a number")),
+                        };
+                        let n = lit.base10_parse::<u32>().map_err(|_| {
+                            Error::new_spanned(lit, "max_len must be at most u32::MAX")
+                        })?;
+                        field_attrs.max_len = Some((n as usize, lit.clone()));
+                    }
+                    NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("local") => {
+                        let lit = match &nv.lit {
+                            Lit::Str(lit) => lit,
+                            lit => {
+                                return Err(Error::new_spanned(
+                                    lit,
+                                    "expected the path of a function, e.g. \"update_fn\"",
+                                ))
+                            }
+                        };
+                        field_attrs.local = Some(nv.path.clone());
+                        field_attrs.local_update = Some(lit.parse()?);
+                    }
+                    NestedMeta::Meta(Meta::Path(path)) if path.is_ident("init_only") => {
+                        field_attrs.init_only = Some(path.clone());
+                    }
+                    NestedMeta::Meta(Meta::Path(path)) if path.is_ident("local") => {
+                        field_attrs.local = Some(path.clone());
+                    }
+                    NestedMeta::Meta(Meta::Path(path)) if path.is_ident("check_eq") => {
+                        field_attrs.check_eq = Some(path.clone());
+                    }
+                    _ => {
+                        return Err(Error::new_spanned(
+                            meta,
+                            "expected max_len = N, init_only, local, local = \"update_fn\" \
+                             or check_eq",
+                        ))
+                    }
+                }
+            }
+        }
+        if let (Some(_), Some(check_eq)) = (&field_attrs.local, &field_attrs.check_eq) {
+            return Err(Error::new_spanned(
+                check_eq,
+                "local fields are not sent from Ethereum, so they can't be checked",
+            ));
+        }
+
+        Ok(field_attrs)
+    }
+
+    /// The first attribute that only applies to `moebius_state` fields.
+    pub fn state_only(&self) -> Option<&Path> {
+        self.init_only
+            .as_ref()
+            .or(self.local.as_ref())
+            .or(self.check_eq.as_ref())
+    }
+
+    /// Whether `UpdateState` may carry the field, to be written or checked.
+    pub fn is_updated(&self) -> bool {
+        self.local.is_none() && (self.init_only.is_none() || self.check_eq.is_some())
+    }
+}
+
+/// Decodes an offset or a length from `src`, a 32-byte ABI word.
+pub fn decode_abi_usize(src: &TokenStream2) -> TokenStream2 {
+    let check = check_padding(src, quote! { ..28 }, quote! { 0 });
+    quote! {{
+        #check
+        let mut word = [0u8; 4];
+        word.copy_from_slice(&#src[28..32]);
+        u32::from_be_bytes(word) as usize
+    }}
+}
+
+fn check_padding(src: &TokenStream2, range: TokenStream2, pad: TokenStream2) -> TokenStream2 {
+    quote! {
+        let pad: u8 = #pad;
+        if #src[#range].iter().any(|b| *b != pad) {
+            return Err(InvalidInstruction.into());
+        }
+    }
+}
@@ -0,0 +1,62 @@
+use inflector::cases::camelcase::to_camel_case;
+use serde_json::json;
+
+use crate::sol_type::SolType;
+
+/// A Solidity library named `<name>Encoder`, whose `pack` function ABI-encodes `fields` the way
+/// `UpdateState` decodes them.
+pub fn library(name: &str, fields: &[(String, SolType)]) -> String {
+    let params: Vec<_> = fields
+        .iter()
+        .map(|(field, sol_type)| {
+            let location = match sol_type {
+                SolType::FixedArray(..)
+                | SolType::Bytes(_)
+                | SolType::String(_)
+                | SolType::Array(..) => " memory",
+                _ => "",
+            };
+            format!("{}{} {}", sol_type.sol_name(), location, param_name(field))
+        })
+        .collect();
+    let args: Vec<_> = fields.iter().map(|(field, _)| param_name(field)).collect();
+
+    format!(
+        "//SPDX-License-Identifier: Unlicense\n\
+         // Generated from the `{name}` Moebius state, do not edit.\n\
+         pragma solidity ^0.7.0;\n\
+         \n\
+         \n\
+         library {name}Encoder {{\n\
+         \x20 // `_packedData` for the `UpdateState` instruction of the target program.\n\
+         \x20 function pack(\n\
+         {params}\n\
+         \x20 )\n\
+         \x20   internal\n\
+         \x20   pure\n\
+         \x20   returns (bytes memory)\n\
+         \x20 {{\n\
+         \x20   return abi.encode({args});\n\
+         \x20 }}\n\
+         }}\n",
+        name = name,
+        params = params
+            .iter()
+            .map(|param| format!("    {}", param))
+            .collect::<Vec<_>>()
+            .join(",\n"),
+        args = args.join(", "),
+    )
+}
+
+/// The ABI JSON solc emits for the library. A library's ABI only lists its `public` and
+/// `external` functions, and `pack` is `internal`, inlined into the contracts calling it.
+pub fn abi_json() -> String {
+    let abi = json!([]);
+    format!("{}\n", abi)
+}
+
+/// `token_amount` is passed as `_tokenAmount`, as in the contracts.
+fn param_name(field: &str) -> String {
+    format!("_{}", to_camel_case(field))
+}
@@ -1 +1,3 @@
-pub use moebius_derive_impl::{moebius_instruction, moebius_state};
+pub use moebius_derive_impl::{moebius_instruction, moebius_program, moebius_state};
+
+pub mod types;
@@ -0,0 +1,54 @@
+//! Types a `#[moebius(typed)]` state holds its fields as.
+
+// `construct_uint!` expands to code these lints flag.
+#![allow(clippy::assign_op_pattern)]
+#![allow(clippy::manual_range_contains)]
+
+use std::fmt;
+
+uint::construct_uint! {
+    /// A 256-bit unsigned integer, for `uint136` to `uint256` fields.
+    ///
+    /// Converts from and into `[u8; 32]` as the big-endian ABI word. Conversions to narrower
+    /// integers are checked with `TryFrom`, and arithmetic with `checked_add`, `checked_mul` and
+    /// so on.
+    pub struct U256(4);
+}
+
+/// An Ethereum address, displayed as `0x` followed by 40 lowercase hex digits.
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Address(pub [u8; 20]);
+
+impl From<[u8; 20]> for Address {
+    fn from(bytes: [u8; 20]) -> Self {
+        Address(bytes)
+    }
+}
+
+impl From<Address> for [u8; 20] {
+    fn from(address: Address) -> Self {
+        address.0
+    }
+}
+
+impl AsRef<[u8]> for Address {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "0x")?;
+        for byte in self.0.iter() {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Address {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
@@ -0,0 +1,322 @@
+use ethabi::{Token, Uint as U256};
+use moebius_derive::moebius_instruction;
+use solana_program::{program_error::ProgramError, pubkey::Pubkey};
+use std::mem::size_of;
+use TestError::*;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum TestError {
+    InvalidInstruction,
+}
+
+impl From<TestError> for ProgramError {
+    fn from(e: TestError) -> Self {
+        ProgramError::Custom(e as u32)
+    }
+}
+
+#[moebius_instruction]
+enum TestInstruction {
+    Initialize {
+        id: bytes32,
+        token: address,
+    },
+    UpdateState {
+        id: bytes32,
+        token: address,
+        amount: uint256,
+        active: bool,
+    },
+}
+
+#[moebius_instruction]
+enum WideInstruction {
+    Initialize {
+        decimals: uint16,
+        offset: int8,
+    },
+    UpdateState {
+        timestamp: uint64,
+        delta: int128,
+        selector: bytes4,
+        prices: [uint256; 2],
+    },
+}
+
+#[moebius_instruction]
+enum DynamicInstruction {
+    Initialize {
+        #[moebius(max_len = 8)]
+        name: string,
+    },
+    UpdateState {
+        id: bytes32,
+        #[moebius(max_len = 8)]
+        name: string,
+        #[moebius(max_len = 4)]
+        amounts: [uint256],
+        active: bool,
+    },
+}
+
+#[moebius_instruction]
+enum NarrowInstruction {
+    Initialize { decimals: uint8 },
+    UpdateState { decimals: uint8, active: bool },
+}
+
+/// Fields named like the locals of the generated code.
+#[moebius_instruction]
+enum ShadowingInstruction {
+    Initialize {
+        start: uint64,
+        buf: uint8,
+        dst: bool,
+        rest: bytes4,
+    },
+    UpdateState {
+        start: uint64,
+        tail: uint32,
+        #[moebius(max_len = 4)]
+        buf: bytes,
+        rest_slice: bool,
+    },
+}
+
+fn word(value: &[u8], pad: u8) -> Vec<u8> {
+    let mut word = vec![pad; 32 - value.len()];
+    word.extend_from_slice(value);
+    word
+}
+
+#[test]
+fn test_update_state_packing() {
+    let check = TestInstruction::UpdateState {
+        id: [1u8; 32],
+        token: [2u8; 20],
+        amount: [3u8; 32],
+        active: 1,
+    };
+    let mut expect = vec![1u8]; // UpdateState tag.
+    expect.extend_from_slice(&[1u8; 32]);
+    expect.extend(word(&[2u8; 20], 0));
+    expect.extend_from_slice(&[3u8; 32]);
+    expect.extend(word(&[1u8], 0));
+    assert_eq!(check.pack().unwrap(), expect);
+    assert_eq!(TestInstruction::unpack(&expect).unwrap(), check);
+}
+
+/// `UpdateState` is packed as `ethers::abi::encode` (ethabi) encodes the fields, `bool` and
+/// `uint8` included.
+#[test]
+fn test_update_state_abi_encoding() {
+    let check = TestInstruction::UpdateState {
+        id: [1u8; 32],
+        token: [2u8; 20],
+        amount: [3u8; 32],
+        active: 1,
+    };
+    let encoded = ethabi::encode(&[
+        Token::FixedBytes(vec![1u8; 32]),
+        Token::Address([2u8; 20].into()),
+        Token::Uint(U256::from_big_endian(&[3u8; 32])),
+        Token::Bool(true),
+    ]);
+    assert_eq!(check.pack().unwrap()[1..], encoded[..]);
+    assert_eq!(
+        TestInstruction::unpack(&[&[1u8], &encoded[..]].concat()).unwrap(),
+        check
+    );
+
+    let check = WideInstruction::UpdateState {
+        timestamp: 1_600_000_000,
+        delta: -2,
+        selector: [0x19, 0xeb, 0x4a, 0x90],
+        prices: [[4u8; 32], [5u8; 32]],
+    };
+    let encoded = ethabi::encode(&[
+        Token::Uint(1_600_000_000u64.into()),
+        Token::Int(U256::MAX - 1),
+        Token::FixedBytes(vec![0x19, 0xeb, 0x4a, 0x90]),
+        Token::FixedArray(vec![
+            Token::Uint(U256::from_big_endian(&[4u8; 32])),
+            Token::Uint(U256::from_big_endian(&[5u8; 32])),
+        ]),
+    ]);
+    assert_eq!(check.pack().unwrap()[1..], encoded[..]);
+
+    let check = DynamicInstruction::UpdateState {
+        id: [1u8; 32],
+        name: "moebius".to_string(),
+        amounts: vec![[3u8; 32], [4u8; 32]],
+        active: 0,
+    };
+    let encoded = ethabi::encode(&[
+        Token::FixedBytes(vec![1u8; 32]),
+        Token::String("moebius".to_string()),
+        Token::Array(vec![
+            Token::Uint(U256::from_big_endian(&[3u8; 32])),
+            Token::Uint(U256::from_big_endian(&[4u8; 32])),
+        ]),
+        Token::Bool(false),
+    ]);
+    assert_eq!(check.pack().unwrap()[1..], encoded[..]);
+
+    let check = NarrowInstruction::UpdateState {
+        decimals: 18,
+        active: 1,
+    };
+    let encoded = ethabi::encode(&[Token::Uint(18.into()), Token::Bool(true)]);
+    assert_eq!(check.pack().unwrap()[1..], encoded[..]);
+    assert_eq!(
+        NarrowInstruction::unpack(&[&[1u8], &encoded[..]].concat()).unwrap(),
+        check
+    );
+}
+
+#[test]
+fn test_wide_types_packing() {
+    let check = WideInstruction::Initialize {
+        moebius_program_id: Pubkey::new(&[2u8; 32]),
+        decimals: 18,
+        offset: -1,
+    };
+    let mut expect = vec![0u8]; // Initialize tag.
+    expect.extend_from_slice(&[2u8; 32]);
+    expect.extend_from_slice(&[18, 0, 0xff]);
+    assert_eq!(check.pack().unwrap(), expect);
+    assert_eq!(WideInstruction::unpack(&expect).unwrap(), check);
+
+    let check = WideInstruction::UpdateState {
+        timestamp: 1_600_000_000,
+        delta: -2,
+        selector: [0x19, 0xeb, 0x4a, 0x90],
+        prices: [[4u8; 32], [5u8; 32]],
+    };
+    let mut expect = vec![1u8]; // UpdateState tag.
+    expect.extend(word(&1_600_000_000u64.to_be_bytes(), 0));
+    expect.extend(word(&[0xfe], 0xff));
+    expect.extend_from_slice(&[0x19, 0xeb, 0x4a, 0x90]);
+    expect.extend_from_slice(&[0u8; 28]);
+    expect.extend_from_slice(&[4u8; 32]);
+    expect.extend_from_slice(&[5u8; 32]);
+    assert_eq!(check.pack().unwrap(), expect);
+    assert_eq!(WideInstruction::unpack(&expect).unwrap(), check);
+
+    // values that don't fit the type are rejected.
+    for &(index, byte) in &[(1, 1u8), (33, 0), (1 + 64 + 4, 1)] {
+        let mut invalid = expect.clone();
+        invalid[index] = byte;
+        assert_eq!(
+            WideInstruction::unpack(&invalid),
+            Err(ProgramError::Custom(InvalidInstruction as u32))
+        );
+    }
+    assert!(WideInstruction::unpack(&expect[..expect.len() - 1]).is_err());
+}
+
+#[test]
+fn test_dynamic_types_packing() {
+    let check = DynamicInstruction::Initialize {
+        moebius_program_id: Pubkey::new(&[2u8; 32]),
+        name: "moebius".to_string(),
+    };
+    let mut expect = vec![0u8]; // Initialize tag.
+    expect.extend_from_slice(&[2u8; 32]);
+    expect.extend_from_slice(&[7, 0, 0, 0]);
+    expect.extend_from_slice(b"moebius\0");
+    assert_eq!(check.pack().unwrap(), expect);
+    assert_eq!(DynamicInstruction::unpack(&expect).unwrap(), check);
+
+    let check = DynamicInstruction::UpdateState {
+        id: [1u8; 32],
+        name: "moebius".to_string(),
+        amounts: vec![[3u8; 32], [4u8; 32]],
+        active: 1,
+    };
+    let mut expect = vec![1u8]; // UpdateState tag.
+    expect.extend_from_slice(&[1u8; 32]);
+    expect.extend(word(&[128], 0)); // offset of name
+    expect.extend(word(&[192], 0)); // offset of amounts
+    expect.extend(word(&[1], 0));
+    expect.extend(word(&[7], 0));
+    expect.extend_from_slice(b"moebius");
+    expect.extend_from_slice(&[0u8; 25]);
+    expect.extend(word(&[2], 0));
+    expect.extend_from_slice(&[3u8; 32]);
+    expect.extend_from_slice(&[4u8; 32]);
+    assert_eq!(check.pack().unwrap(), expect);
+    assert_eq!(DynamicInstruction::unpack(&expect).unwrap(), check);
+
+    // longer than max_len.
+    let mut invalid = expect.clone();
+    invalid[1 + 128 + 31] = 9;
+    assert!(DynamicInstruction::unpack(&invalid).is_err());
+    // offset out of bounds.
+    let mut invalid = expect.clone();
+    invalid[1 + 32 + 30] = 1;
+    assert!(DynamicInstruction::unpack(&invalid).is_err());
+}
+
+#[test]
+fn test_pack_too_long() {
+    let invalid_instruction = ProgramError::Custom(InvalidInstruction as u32);
+    let check = DynamicInstruction::Initialize {
+        moebius_program_id: Pubkey::new(&[2u8; 32]),
+        name: "moebius!!".to_string(),
+    };
+    assert_eq!(check.pack().unwrap_err(), invalid_instruction);
+
+    let check = DynamicInstruction::UpdateState {
+        id: [1u8; 32],
+        name: "moebius".to_string(),
+        amounts: vec![[3u8; 32]; 5],
+        active: 1,
+    };
+    assert_eq!(check.pack().unwrap_err(), invalid_instruction);
+    assert_eq!(
+        DynamicInstruction::update_state(
+            &Pubkey::new(&[3u8; 32]),
+            &Pubkey::new(&[4u8; 32]),
+            &Pubkey::new(&[5u8; 32]),
+            [1u8; 32],
+            "moebius!!".to_string(),
+            vec![],
+            1,
+        )
+        .unwrap_err(),
+        invalid_instruction
+    );
+}
+
+#[test]
+fn test_generated_names_packing() {
+    let check = ShadowingInstruction::Initialize {
+        moebius_program_id: Pubkey::new(&[2u8; 32]),
+        start: 5,
+        buf: 6,
+        dst: 1,
+        rest: [7u8; 4],
+    };
+    let mut expect = vec![0u8]; // Initialize tag.
+    expect.extend_from_slice(&[2u8; 32]);
+    expect.extend_from_slice(&[5, 0, 0, 0, 0, 0, 0, 0, 6, 1, 7, 7, 7, 7]);
+    assert_eq!(check.pack().unwrap(), expect);
+    assert_eq!(ShadowingInstruction::unpack(&expect).unwrap(), check);
+
+    let check = ShadowingInstruction::UpdateState {
+        start: 5,
+        tail: 6,
+        buf: vec![7u8; 3],
+        rest_slice: 1,
+    };
+    let mut expect = vec![1u8]; // UpdateState tag.
+    expect.extend(word(&[5], 0));
+    expect.extend(word(&[6], 0));
+    expect.extend(word(&[128], 0)); // offset of buf
+    expect.extend(word(&[1], 0));
+    expect.extend(word(&[3], 0));
+    expect.extend(word(&[7u8; 3], 0).into_iter().rev());
+    assert_eq!(check.pack().unwrap(), expect);
+    assert_eq!(ShadowingInstruction::unpack(&expect).unwrap(), check);
+}
@@ -0,0 +1,80 @@
+//! Visibility, doc comments and other attributes are kept on the generated types.
+#![deny(missing_docs)]
+
+use std::collections::HashSet;
+
+/// Types generated in a module, to check their visibility.
+pub mod oracle {
+    use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
+    use moebius_derive::{moebius_instruction, moebius_state};
+    use solana_program::{
+        program_error::ProgramError,
+        program_pack::{IsInitialized, Pack, Sealed},
+        pubkey::Pubkey,
+    };
+    use std::mem::size_of;
+    use OracleError::*;
+
+    /// Errors of the oracle.
+    #[derive(Clone, Copy, Debug)]
+    pub enum OracleError {
+        /// The instruction could not be unpacked.
+        InvalidInstruction,
+    }
+
+    impl From<OracleError> for ProgramError {
+        fn from(e: OracleError) -> Self {
+            ProgramError::Custom(e as u32)
+        }
+    }
+
+    /// The price of a token.
+    #[moebius_state]
+    #[derive(Eq, Hash)]
+    pub struct OracleState {
+        /// The token.
+        #[moebius(init_only, check_eq)]
+        pub token: address,
+        /// Its price.
+        pub price: uint64,
+        /// Not visible outside of the module.
+        #[moebius(local)]
+        pub(super) slot: uint64,
+    }
+
+    /// The instructions of the oracle.
+    #[moebius_instruction]
+    #[derive(Eq, Hash)]
+    pub enum OracleInstruction {
+        /// Sets the token.
+        Initialize {
+            /// The token.
+            token: address,
+        },
+        /// Sets the price.
+        UpdateState {
+            /// The token.
+            token: address,
+            /// The price.
+            price: uint64,
+        },
+    }
+}
+
+fn main() {
+    let state = oracle::OracleState {
+        token: [1u8; 20],
+        price: 2,
+        slot: 3,
+        ..Default::default()
+    };
+    let states: HashSet<_> = vec![state.clone(), state].into_iter().collect();
+    assert_eq!(states.len(), 1);
+
+    let instruction = oracle::OracleInstruction::UpdateState {
+        token: [1u8; 20],
+        price: 2,
+    };
+    let instructions: HashSet<_> = vec![instruction.clone(), instruction].into_iter().collect();
+    assert_eq!(instructions.len(), 1);
+}
@@ -0,0 +1,159 @@
+use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
+use moebius_derive::{
+    moebius_state,
+    types::{Address, U256},
+};
+use solana_program::{
+    program_error::ProgramError,
+    program_pack::{IsInitialized, Pack, Sealed},
+    pubkey::Pubkey,
+};
+use std::convert::TryFrom;
+
+/// Fields named like the locals of the generated code.
+#[moebius_state]
+struct ShadowingState {
+    src: uint64,
+    dst: uint8,
+    authority_src: bool,
+    #[moebius(max_len = 4)]
+    len: bytes,
+}
+
+#[moebius_state]
+struct HistoryState {
+    prices: [uint64; 48],
+    #[moebius(max_len = 40)]
+    labels: [bytes4],
+}
+
+#[moebius_state]
+struct OracleState {
+    #[moebius(init_only, check_eq)]
+    token: address,
+    #[moebius(init_only)]
+    decimals: uint8,
+    amount: uint256,
+    #[moebius(local)]
+    slot: uint64,
+}
+
+#[moebius_state]
+#[moebius(typed)]
+struct TypedOracleState {
+    token: address,
+    decimals: uint8,
+    amount: uint256,
+    prices: [uint256; 2],
+    #[moebius(max_len = 2)]
+    holders: [address],
+}
+
+#[moebius_state]
+struct UntypedOracleState {
+    token: address,
+    decimals: uint8,
+    amount: uint256,
+    prices: [uint256; 2],
+    #[moebius(max_len = 2)]
+    holders: [address],
+}
+
+#[test]
+fn test_generated_names_packing() {
+    let state = ShadowingState {
+        is_initialized: true,
+        authority: Pubkey::new(&[1u8; 32]),
+        src: 5,
+        dst: 6,
+        authority_src: 1,
+        len: vec![7u8; 3],
+    };
+    let mut packed = vec![0u8; ShadowingState::LEN];
+    ShadowingState::pack(state.clone(), &mut packed).unwrap();
+    assert_eq!(ShadowingState::unpack(&packed).unwrap(), state);
+}
+
+#[test]
+fn test_long_array_state() {
+    let mut state = HistoryState::default();
+    assert_eq!(state.prices, [0u64; 48]);
+    assert!(state.labels.is_empty());
+
+    state.is_initialized = true;
+    state.prices[47] = 7;
+    state.labels = vec![[1u8; 4]; 40];
+    let mut packed = vec![0u8; HistoryState::LEN];
+    HistoryState::pack(state.clone(), &mut packed).unwrap();
+    assert_eq!(HistoryState::unpack(&packed).unwrap(), state);
+}
+
+#[test]
+fn test_pack_too_long() {
+    let mut packed = vec![0u8; ShadowingState::LEN];
+    let state = ShadowingState {
+        len: vec![7u8; 5],
+        ..ShadowingState::default()
+    };
+    assert_eq!(
+        ShadowingState::pack(state, &mut packed),
+        Err(ProgramError::InvalidAccountData)
+    );
+
+    let mut packed = vec![0u8; HistoryState::LEN];
+    let state = HistoryState {
+        labels: vec![[1u8; 4]; 41],
+        ..HistoryState::default()
+    };
+    assert_eq!(
+        HistoryState::pack(state, &mut packed),
+        Err(ProgramError::InvalidAccountData)
+    );
+    assert!(packed.iter().all(|&b| b == 0));
+}
+
+#[test]
+fn test_typed_state_packing() {
+    let mut amount = [0u8; 32];
+    amount[16..].copy_from_slice(&u128::MAX.to_be_bytes());
+    let untyped = UntypedOracleState {
+        is_initialized: true,
+        authority: Pubkey::new(&[1u8; 32]),
+        token: [0xabu8; 20],
+        decimals: 18,
+        amount,
+        prices: [[0xffu8; 32], [0u8; 32]],
+        holders: vec![[0xcdu8; 20]],
+    };
+    let mut packed = vec![0u8; UntypedOracleState::LEN];
+    UntypedOracleState::pack(untyped, &mut packed).unwrap();
+
+    // the typed state is packed the same way.
+    let typed = TypedOracleState::unpack(&packed).unwrap();
+    assert_eq!(typed.token, Address([0xabu8; 20]));
+    assert_eq!(typed.amount, U256::from(u128::MAX));
+    // so are the elements of arrays.
+    assert_eq!(typed.prices, [U256::from([0xffu8; 32]), U256::zero()]);
+    assert_eq!(typed.holders, vec![Address([0xcdu8; 20])]);
+    let mut repacked = vec![0u8; TypedOracleState::LEN];
+    TypedOracleState::pack(typed.clone(), &mut repacked).unwrap();
+    assert_eq!(repacked, packed);
+
+    assert_eq!(
+        typed.token.to_string(),
+        "0xabababababababababababababababababababab"
+    );
+    assert_eq!(u128::try_from(typed.amount), Ok(u128::MAX));
+    let doubled = typed.amount.checked_mul(U256::from(2)).unwrap();
+    assert!(u128::try_from(doubled).is_err());
+    assert_eq!(U256::MAX.checked_add(U256::one()), None);
+}
+
+#[test]
+fn test_solidity_encoder_fields() {
+    // every field is held in the account.
+    assert_eq!(OracleState::LEN, 33 + 20 + 1 + 32 + 8);
+
+    // only the checked and the updated ones are sent from Ethereum.
+    assert!(OracleState::SOLIDITY_ENCODER.contains("return abi.encode(_token, _amount);"));
+}
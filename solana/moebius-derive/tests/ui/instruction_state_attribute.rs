@@ -0,0 +1,12 @@
+use moebius_derive::moebius_instruction;
+
+#[moebius_instruction]
+pub enum OracleInstruction {
+    Initialize { token: address },
+    UpdateState {
+        #[moebius(check_eq)]
+        token: address,
+    },
+}
+
+fn main() {}
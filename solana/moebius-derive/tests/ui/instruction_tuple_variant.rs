@@ -0,0 +1,9 @@
+use moebius_derive::moebius_instruction;
+
+#[moebius_instruction]
+pub enum AnotherInstruction {
+    Initialize { id: bytes32 },
+    UpdateState(bytes32, address),
+}
+
+fn main() {}
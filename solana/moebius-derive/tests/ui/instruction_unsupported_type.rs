@@ -0,0 +1,9 @@
+use moebius_derive::moebius_instruction;
+
+#[moebius_instruction]
+pub enum AnotherInstruction {
+    Initialize { id: bytes32 },
+    UpdateState { id: bytes32, amount: u64 },
+}
+
+fn main() {}
@@ -0,0 +1,16 @@
+use moebius_derive::moebius_program;
+
+moebius_program! {
+    pub struct OracleState {
+        #[moebius(check_eq)]
+        pub token: address,
+        pub amount: uint256,
+    }
+
+    pub enum OracleInstruction {
+        Initialize { amount: uint256 },
+        UpdateState { token: address, amount: uint256 },
+    }
+}
+
+fn main() {}
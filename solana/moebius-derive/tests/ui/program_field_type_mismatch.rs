@@ -0,0 +1,14 @@
+use moebius_derive::moebius_program;
+
+moebius_program! {
+    pub struct AnotherState {
+        pub id: bytes32,
+    }
+
+    pub enum AnotherInstruction {
+        Initialize { id: bytes32 },
+        UpdateState { id: bytes16 },
+    }
+}
+
+fn main() {}
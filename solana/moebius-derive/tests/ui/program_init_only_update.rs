@@ -0,0 +1,16 @@
+use moebius_derive::moebius_program;
+
+moebius_program! {
+    pub struct OracleState {
+        #[moebius(init_only)]
+        pub token: address,
+        pub amount: uint256,
+    }
+
+    pub enum OracleInstruction {
+        Initialize { token: address },
+        UpdateState { token: address, amount: uint256 },
+    }
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use moebius_derive::moebius_state;
+
+#[moebius_state]
+pub struct AnotherState {
+    #[moebius(max_len = 4)]
+    names: [string],
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use moebius_derive::moebius_state;
+
+#[moebius_state]
+pub struct OracleState {
+    #[moebius(local = 1)]
+    slot: uint64,
+}
+
+fn main() {}
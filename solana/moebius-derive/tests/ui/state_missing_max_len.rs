@@ -0,0 +1,8 @@
+use moebius_derive::moebius_state;
+
+#[moebius_state]
+pub struct AnotherState {
+    name: string,
+}
+
+fn main() {}
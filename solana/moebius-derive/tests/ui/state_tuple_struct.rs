@@ -0,0 +1,6 @@
+use moebius_derive::moebius_state;
+
+#[moebius_state]
+pub struct AnotherState(bytes32, address);
+
+fn main() {}
@@ -0,0 +1,8 @@
+use moebius_derive::moebius_state;
+
+#[moebius_state]
+pub struct AnotherState {
+    amount: uint12,
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use moebius_derive::moebius_state;
+
+#[moebius_state]
+#[moebius(max_len = 4)]
+pub struct AnotherState {
+    amount: uint256,
+}
+
+fn main() {}
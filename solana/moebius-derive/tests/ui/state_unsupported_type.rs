@@ -0,0 +1,9 @@
+use moebius_derive::moebius_state;
+
+#[moebius_state]
+pub struct AnotherState {
+    id: bytes32,
+    token: adress,
+}
+
+fn main() {}